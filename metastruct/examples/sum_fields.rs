@@ -0,0 +1,119 @@
+//! Sum the numeric fields of a struct by hand, and with mappings using static and dynamic dispatch.
+//!
+//! With `static_dispatch` the closure is inlined, and `sum_static` compiles to the same code as
+//! `sum_by_hand` in release builds, which `tests/codegen.rs` checks. The optimiser can usually
+//! devirtualise the calls through `dyn FnMut` in a function as small as `sum_dyn` too, but it then
+//! only inlines closures which are small enough, as each closure is also referenced by the trait
+//! object's vtable. The `checksum` functions apply a closure too large to be inlined this way, and
+//! only `checksum_static` compiles to the same code as `checksum_by_hand`. The functions are also
+//! compared by `benches/mapping.rs`.
+// The closures convert fields of several types, some of which are already `u64`.
+#![allow(clippy::useless_conversion)]
+use metastruct::metastruct;
+
+#[metastruct(mappings(
+    map_state_numbers_static(exclude(root), static_dispatch),
+    map_state_numbers_dyn(exclude(root)),
+))]
+pub struct State {
+    pub slot: u64,
+    pub epoch: u64,
+    pub validators: u32,
+    pub root: [u8; 32],
+    pub balance: u64,
+    pub flags: u8,
+    pub fork: u16,
+}
+
+#[inline(never)]
+#[no_mangle]
+pub fn sum_by_hand(state: &State) -> u64 {
+    state.slot
+        + state.epoch
+        + u64::from(state.validators)
+        + state.balance
+        + u64::from(state.flags)
+        + u64::from(state.fork)
+}
+
+#[inline(never)]
+#[no_mangle]
+pub fn sum_static(state: &State) -> u64 {
+    let mut sum = 0;
+    map_state_numbers_static!(state, |_, x| sum += u64::from(*x));
+    sum
+}
+
+#[inline(never)]
+#[no_mangle]
+pub fn sum_dyn(state: &State) -> u64 {
+    let mut sum = 0;
+    map_state_numbers_dyn!(state, |_, x| sum += u64::from(*x));
+    sum
+}
+
+/// Scramble the bits of a field, as an example of a computation too large to inline everywhere.
+#[inline(always)]
+fn scramble(x: u64) -> u64 {
+    let mut x = x;
+    for _ in 0..64 {
+        x ^= x >> 33;
+        x = x.wrapping_mul(0xff51afd7ed558ccd);
+    }
+    x
+}
+
+#[inline(never)]
+#[no_mangle]
+pub fn checksum_by_hand(state: &State) -> u64 {
+    let mut checksum = 0u64;
+    checksum = checksum.wrapping_add(scramble(state.slot));
+    checksum = checksum.wrapping_add(scramble(state.epoch));
+    checksum = checksum.wrapping_add(scramble(u64::from(state.validators)));
+    checksum = checksum.wrapping_add(scramble(state.balance));
+    checksum = checksum.wrapping_add(scramble(u64::from(state.flags)));
+    checksum = checksum.wrapping_add(scramble(u64::from(state.fork)));
+    checksum
+}
+
+#[inline(never)]
+#[no_mangle]
+pub fn checksum_static(state: &State) -> u64 {
+    let mut checksum = 0u64;
+    map_state_numbers_static!(state, |_, x| {
+        checksum = checksum.wrapping_add(scramble(u64::from(*x)))
+    });
+    checksum
+}
+
+#[inline(never)]
+#[no_mangle]
+pub fn checksum_dyn(state: &State) -> u64 {
+    let mut checksum = 0u64;
+    map_state_numbers_dyn!(state, |_, x| {
+        checksum = checksum.wrapping_add(scramble(u64::from(*x)))
+    });
+    checksum
+}
+
+pub fn state() -> State {
+    State {
+        slot: 1,
+        epoch: 2,
+        validators: 3,
+        root: [0; 32],
+        balance: 4,
+        flags: 5,
+        fork: 6,
+    }
+}
+
+fn main() {
+    let state = state();
+    println!("by hand: {}", sum_by_hand(&state));
+    println!("static dispatch: {}", sum_static(&state));
+    println!("dynamic dispatch: {}", sum_dyn(&state));
+    println!("checksum by hand: {}", checksum_by_hand(&state));
+    println!("checksum with static dispatch: {}", checksum_static(&state));
+    println!("checksum with dynamic dispatch: {}", checksum_dyn(&state));
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+
+/// Error for a field name which doesn't match any of the expected fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownFieldError {
+    pub name: String,
+    pub valid_names: &'static [&'static str],
+}
+
+impl fmt::Display for UnknownFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown field `{}`, expected one of: ", self.name)?;
+        for (i, name) in self.valid_names.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "`{name}`")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnknownFieldError {}
+
+/// Error for a field index greater than or equal to the number of fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldIndexError {
+    pub index: usize,
+    pub num_fields: usize,
+}
+
+impl fmt::Display for FieldIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "field index {} out of range for {} fields",
+            self.index, self.num_fields
+        )
+    }
+}
+
+impl std::error::Error for FieldIndexError {}
+
+/// Error setting a field from a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetFieldError {
+    /// No field with the given name can be set.
+    UnknownField(UnknownFieldError),
+    /// The value could not be parsed as the field's type.
+    InvalidValue {
+        name: &'static str,
+        value: String,
+        error: String,
+    },
+}
+
+impl fmt::Display for SetFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownField(error) => error.fmt(f),
+            Self::InvalidValue { name, value, error } => {
+                write!(f, "invalid value `{value}` for field `{name}`: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SetFieldError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::UnknownField(error) => Some(error),
+            Self::InvalidValue { .. } => None,
+        }
+    }
+}
+
+impl From<UnknownFieldError> for SetFieldError {
+    fn from(error: UnknownFieldError) -> Self {
+        Self::UnknownField(error)
+    }
+}
+
+/// Error constructing a struct from a map of keys to string values.
+///
+/// Lists every missing key and every value that failed to parse, not just the first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FromMapError {
+    /// Keys of required fields without a value.
+    pub missing: Vec<String>,
+    /// Values that could not be parsed as their field's type.
+    pub invalid: Vec<InvalidEntry>,
+}
+
+/// Value that could not be parsed as the type of the field it was given for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEntry {
+    pub key: String,
+    pub value: String,
+    pub error: String,
+}
+
+impl FromMapError {
+    /// Whether no errors have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.invalid.is_empty()
+    }
+}
+
+impl fmt::Display for FromMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.missing.is_empty() {
+            write!(f, "missing keys: ")?;
+            for (i, key) in self.missing.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "`{key}`")?;
+            }
+        }
+        if !self.invalid.is_empty() {
+            if !self.missing.is_empty() {
+                write!(f, "; ")?;
+            }
+            write!(f, "invalid values: ")?;
+            for (i, InvalidEntry { key, value, error }) in self.invalid.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "`{key}` = `{value}` ({error})")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FromMapError {}
@@ -1,6 +1,10 @@
 #[cfg(feature = "macro")]
 pub use metastruct_macro::metastruct;
 
+pub use error::{FieldIndexError, FromMapError, InvalidEntry, SetFieldError, UnknownFieldError};
+
+mod error;
+
 /// Trait for structs with a countable number of fields.
 ///
 /// The `Selector` type can be used to select different subsets of fields.
@@ -11,6 +15,43 @@ pub trait NumFields<Selector> {
     const NUM_FIELDS: usize;
 }
 
+/// Trait for structs with a known list of field types.
+///
+/// `Types` is a tuple of the types of the fields selected by `Selector`, in order. Flattened
+/// fields contribute a nested tuple of the types of their own selected fields.
+///
+/// Implementations of this trait are generated alongside `NumFields` by `num_fields` entries with
+/// the `field_types` flag, and allow generic code to require bounds on every selected field type
+/// with a single clause.
+pub trait FieldTypes<Selector> {
+    type Types;
+}
+
+/// Trait for structs whose number of fields depends on a runtime context.
+///
+/// This is implemented for `num_fields` selections containing fields with `only_if` predicates,
+/// in which case `NumFields::NUM_FIELDS` is the number of fields when all predicates hold.
+pub trait NumFieldsWithContext<Selector, Context: ?Sized>: NumFields<Selector> {
+    fn num_fields(context: &Context) -> usize;
+}
+
+/// Trait for marker types identifying a single field of the struct `S`.
+///
+/// Implementations of this trait are intended to be generated using the `metastruct` macro
+/// and the `lenses` attribute, which allows code to be generic over *which* field it accesses.
+pub trait Field<S> {
+    /// Type of the field.
+    type Value;
+    /// Name of the field.
+    const NAME: &'static str;
+    /// Position of the field in the struct definition, counting from zero.
+    const INDEX: usize;
+
+    fn get(value: &S) -> &Self::Value;
+
+    fn get_mut(value: &mut S) -> &mut Self::Value;
+}
+
 pub mod selectors {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum AllFields {}
@@ -0,0 +1,238 @@
+use metastruct::{metastruct, selectors::AllFields, Field, FieldTypes, NumFields};
+use std::collections::{BTreeMap, HashMap};
+
+#[metastruct(mappings(map_fields(), map_fields_mut(mutable)), num_fields(all()))]
+#[cfg_attr(
+    all(),
+    metastruct(mappings(map_enabled_by_struct_cfg_attr(exclude(a))))
+)]
+#[cfg_attr(any(), metastruct(mappings(map_fields())))]
+#[derive(Debug, PartialEq)]
+pub struct Foo {
+    a: u64,
+    #[cfg(not(any()))]
+    b: u64,
+    #[cfg(any())]
+    c: String,
+    #[cfg_attr(all(), metastruct(exclude))]
+    d: u64,
+    #[cfg_attr(any(), metastruct(exclude))]
+    #[cfg_attr(all(), allow(unused))]
+    e: u64,
+}
+
+fn foo() -> Foo {
+    Foo {
+        a: 1,
+        b: 10,
+        d: 100,
+        e: 1000,
+    }
+}
+
+#[test]
+fn cfg_fields_mapping() {
+    let mut visited = vec![];
+    map_fields!(&foo(), |i, x| visited.push((i, *x)));
+    assert_eq!(visited, vec![(0, 1), (1, 10), (2, 1000)]);
+}
+
+#[test]
+fn cfg_fields_mutable_mapping() {
+    let mut foo = foo();
+    map_fields_mut!(&mut foo, |_, x| *x += 1);
+    assert_eq!(
+        foo,
+        Foo {
+            a: 2,
+            b: 11,
+            d: 100,
+            e: 1001
+        }
+    );
+}
+
+#[test]
+fn cfg_attr_on_struct() {
+    let mut total = 0;
+    map_enabled_by_struct_cfg_attr!(&foo(), |_, x| total += *x);
+    assert_eq!(total, 1010);
+}
+
+#[test]
+fn cfg_num_fields() {
+    assert_eq!(<Foo as NumFields<AllFields>>::NUM_FIELDS, 3);
+}
+
+#[metastruct(mappings(map_included(include(a, c))))]
+pub struct Included {
+    a: u64,
+    #[cfg(any())]
+    c: u64,
+}
+
+#[test]
+fn include_disabled_field() {
+    let mut visited = vec![];
+    map_included!(&Included { a: 1 }, |i, x| visited.push((i, *x)));
+    assert_eq!(visited, vec![(0, 1)]);
+}
+
+/// Items other than mapping macros are generated once, with the `cfg` of each field.
+#[metastruct(
+    mappings(map_numbers(only_types(u64))),
+    num_fields(all(field_types)),
+    field_enum,
+    lenses,
+    dyn_access(number(only_types(u64))),
+    string_access(only_types(u64)),
+    from_kv(only_types(u64)),
+    maps(numbers(only_types(u64))),
+    iters(numbers(only_types(u64))),
+    tuples(values(only_types(u64))),
+    views(
+        numbers(only_types(u64), owned),
+        labels(except_types(u64), owned),
+        gated(include(c, removed)),
+    ),
+    splits(relabel(views(numbers, labels), mutable(labels))),
+    partitions(parts(views(numbers, labels)))
+)]
+#[derive(Debug, Default, PartialEq)]
+pub struct Gated {
+    #[cfg(any())]
+    removed: u64,
+    a: u64,
+    label: String,
+    #[cfg(not(any()))]
+    c: u64,
+    #[cfg_attr(any(), metastruct(exclude))]
+    d: u64,
+}
+
+fn gated() -> Gated {
+    Gated {
+        a: 1,
+        label: "gated".to_string(),
+        c: 10,
+        d: 100,
+    }
+}
+
+#[test]
+fn gated_num_fields() {
+    assert_eq!(<Gated as NumFields<AllFields>>::NUM_FIELDS, 4);
+    let _: <Gated as FieldTypes<AllFields>>::Types = (1u64, String::new(), 10u64, 100u64);
+}
+
+#[test]
+fn gated_mapping() {
+    let mut visited = vec![];
+    map_numbers!(&gated(), |i, x| visited.push((i, *x)));
+    assert_eq!(visited, vec![(0, 1), (1, 10), (2, 100)]);
+}
+
+#[test]
+fn gated_field_enum() {
+    assert_eq!(
+        GatedField::ALL,
+        [
+            GatedField::A,
+            GatedField::Label,
+            GatedField::C,
+            GatedField::D
+        ]
+    );
+    assert_eq!(GatedField::NAMES, ["a", "label", "c", "d"]);
+    for (i, field) in GatedField::ALL.into_iter().enumerate() {
+        assert_eq!(field.index(), i);
+        assert_eq!(GatedField::try_from(i), Ok(field));
+        assert_eq!(field.name().parse(), Ok(field));
+    }
+    assert!(GatedField::try_from(4).is_err());
+}
+
+#[test]
+fn gated_lenses() {
+    assert_eq!(gated_fields::C::INDEX, 2);
+    assert_eq!(gated_fields::D::INDEX, 3);
+    assert_eq!(*gated_fields::D::get(&gated()), 100);
+}
+
+#[test]
+fn gated_dyn_access() {
+    let gated = gated();
+    let by_index = |i| {
+        gated
+            .get_number_by_index(i)
+            .and_then(|x| x.downcast_ref::<u64>())
+    };
+    assert_eq!(
+        (by_index(1), by_index(2), by_index(3)),
+        (Some(&10), Some(&100), None)
+    );
+    assert!(gated.get_number("d").is_some());
+}
+
+#[test]
+fn gated_string_access() {
+    let mut gated = gated();
+    gated.set_field_from_str("d", "5").unwrap();
+    assert_eq!(gated.field_to_string("d").unwrap(), "5");
+    assert_eq!(gated.field_to_string("c").unwrap(), "10");
+    assert!(gated.field_to_string("removed").is_err());
+}
+
+#[test]
+fn gated_from_kv() {
+    let map = HashMap::from(
+        [("A", "1"), ("C", "10"), ("D", "100")].map(|(k, v)| (k.to_string(), v.to_string())),
+    );
+    assert_eq!(
+        Gated::from_map(&map).unwrap(),
+        Gated {
+            label: String::new(),
+            ..gated()
+        }
+    );
+}
+
+#[test]
+fn gated_maps_and_iters() {
+    let gated = gated();
+    let numbers = gated.to_numbers();
+    assert_eq!(numbers, BTreeMap::from([("a", 1), ("c", 10), ("d", 100)]));
+    assert_eq!(
+        Gated::try_from_numbers(numbers).unwrap(),
+        Gated {
+            label: String::new(),
+            ..gated
+        }
+    );
+    assert_eq!(gated.as_numbers_array(), [&1, &10, &100]);
+}
+
+#[test]
+fn gated_tuples() {
+    assert_eq!(gated().into_values(), (1, 10, 100));
+    assert_eq!(
+        Gated::from_values((1, 10, 100)),
+        Gated {
+            label: String::new(),
+            ..gated()
+        }
+    );
+}
+
+#[test]
+fn gated_views() {
+    let mut gated = gated();
+    assert_eq!(*gated.as_gated_ref().c, 10);
+    let (numbers, labels) = gated.split_relabel_mut();
+    labels.label.push_str(&numbers.d.to_string());
+    assert_eq!(gated.label, "gated100");
+
+    let (numbers, labels) = gated.into_parts();
+    assert_eq!((numbers.a, numbers.c, numbers.d), (1, 10, 100));
+    assert_eq!(Gated::from_parts(numbers, labels).label, "gated100");
+}
@@ -0,0 +1,82 @@
+//! Check that mappings with `static_dispatch` compile to the same code as the hand-written
+//! equivalent, and that mappings with dynamic dispatch may not, by comparing the assembly of the
+//! `sum_fields` example in release mode.
+//!
+//! The assembly is only parsed for x86-64 Linux targets.
+#![cfg(all(target_arch = "x86_64", target_os = "linux"))]
+use std::{fs, path::Path, process::Command};
+
+/// Instructions of the function `name` in `asm`, following aliases, without labels or directives.
+fn instructions(asm: &str, name: &str) -> Vec<String> {
+    let lines = asm.lines().map(str::trim).collect::<Vec<_>>();
+    let is_symbol = |symbol: &str| symbol == name || symbol == format!("_{name}");
+
+    // Identical functions may be merged, leaving one as an alias of the other.
+    if let Some(target) = lines.iter().find_map(|line| {
+        let (symbol, target) = line.split_once(" = ")?;
+        is_symbol(symbol).then(|| target.trim_start_matches('_').to_string())
+    }) {
+        return instructions(asm, &target);
+    }
+
+    let start = lines
+        .iter()
+        .position(|line| line.strip_suffix(':').is_some_and(is_symbol))
+        .unwrap_or_else(|| panic!("function `{name}` not found"));
+    lines[start + 1..]
+        .iter()
+        .take_while(|line| !line.starts_with(".cfi_endproc") && !line.starts_with(".Lfunc_end"))
+        .filter(|line| !line.is_empty() && !line.starts_with('.') && !line.ends_with(':'))
+        .map(|line| {
+            // Local labels are numbered differently in each function.
+            line.split_whitespace()
+                .map(|word| {
+                    if word.contains("LBB") {
+                        "<label>"
+                    } else {
+                        word
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+#[test]
+fn static_dispatch_matches_hand_written() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = manifest_dir.join("../target/codegen");
+    let status = Command::new(env!("CARGO"))
+        .args(["rustc", "--release", "--example", "sum_fields", "--"])
+        .args(["--emit", "asm", "-C", "codegen-units=1"])
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .current_dir(manifest_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let examples_dir = target_dir.join("release/examples");
+    let asm_path = fs::read_dir(&examples_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "s")
+                && path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("sum_fields-"))
+        })
+        .max_by_key(|path| path.metadata().unwrap().modified().unwrap())
+        .expect("assembly of the example not found");
+    let asm = fs::read_to_string(asm_path).unwrap();
+
+    let sum_by_hand = instructions(&asm, "sum_by_hand");
+    assert!(!sum_by_hand.is_empty());
+    assert_eq!(instructions(&asm, "sum_static"), sum_by_hand);
+
+    // The closure of the dynamically dispatched checksum is too large to be inlined at each field.
+    let checksum_by_hand = instructions(&asm, "checksum_by_hand");
+    assert!(!checksum_by_hand.is_empty());
+    assert_eq!(instructions(&asm, "checksum_static"), checksum_by_hand);
+    assert_ne!(instructions(&asm, "checksum_dyn"), checksum_by_hand);
+}
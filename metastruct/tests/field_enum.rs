@@ -0,0 +1,88 @@
+use metastruct::{metastruct, FieldIndexError, UnknownFieldError};
+use std::collections::BTreeMap;
+
+#[metastruct(
+    field_enum(exclude(cache)),
+    mappings(
+        map_config_fields(field_enum, exclude(cache)),
+        map_config_fields_mut(field_enum, mutable, exclude(cache)),
+    )
+)]
+#[derive(Debug, Default)]
+pub struct Config {
+    pub port: u64,
+    pub max_peers: u64,
+    cache: Vec<u8>,
+    pub target_peers: u64,
+}
+
+#[test]
+fn all_names_and_indices() {
+    assert_eq!(
+        ConfigField::ALL,
+        [
+            ConfigField::Port,
+            ConfigField::MaxPeers,
+            ConfigField::TargetPeers
+        ]
+    );
+    for (i, field) in ConfigField::ALL.into_iter().enumerate() {
+        assert_eq!(field.index(), i);
+        assert_eq!(ConfigField::try_from(i), Ok(field));
+        assert_eq!(field.name().parse::<ConfigField>(), Ok(field));
+    }
+    assert_eq!(ConfigField::MaxPeers.name(), "max_peers");
+    assert_eq!(ConfigField::TargetPeers.to_string(), "target_peers");
+}
+
+#[test]
+fn errors() {
+    let err = "cache".parse::<ConfigField>().unwrap_err();
+    assert_eq!(
+        err,
+        UnknownFieldError {
+            name: "cache".to_string(),
+            valid_names: &["port", "max_peers", "target_peers"],
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "unknown field `cache`, expected one of: `port`, `max_peers`, `target_peers`"
+    );
+    assert_eq!(
+        ConfigField::try_from(3),
+        Err(FieldIndexError {
+            index: 3,
+            num_fields: 3
+        })
+    );
+}
+
+#[test]
+fn mapping_with_field_enum() {
+    let mut config = Config {
+        port: 9000,
+        max_peers: 100,
+        cache: vec![],
+        target_peers: 50,
+    };
+    map_config_fields_mut!(&mut config, |field, value| {
+        if field == ConfigField::MaxPeers {
+            *value *= 2;
+        }
+    });
+
+    let mut values = BTreeMap::new();
+    map_config_fields!(&config, |field, value| {
+        values.insert(field, *value);
+    });
+    assert_eq!(
+        values,
+        BTreeMap::from([
+            (ConfigField::Port, 9000),
+            (ConfigField::MaxPeers, 200),
+            (ConfigField::TargetPeers, 50),
+        ])
+    );
+    assert!(config.cache.is_empty());
+}
@@ -0,0 +1,80 @@
+use metastruct::{metastruct, NumFields};
+
+#[metastruct(
+    iters(balances(only_types(u64), selector = "BalanceFields")),
+    num_fields(balances(only_types(u64), selector = "BalanceFields"))
+)]
+#[derive(Debug, Default)]
+pub struct Account {
+    pub free: u64,
+    pub reserved: u64,
+    pub owner: String,
+    pub frozen: u64,
+}
+
+#[metastruct(iters(values(exclude(name))))]
+pub struct Pair<T> {
+    pub name: &'static str,
+    pub left: T,
+    pub right: T,
+}
+
+fn account() -> Account {
+    Account {
+        free: 10,
+        reserved: 5,
+        owner: "alice".to_string(),
+        frozen: 1,
+    }
+}
+
+#[test]
+fn iterator_adapters() {
+    let account = account();
+    assert_eq!(account.iter_balances().sum::<u64>(), 16);
+    assert_eq!(account.iter_balances().len(), 3);
+    assert_eq!(account.iter_balances().next_back(), Some(&1));
+    assert_eq!(
+        account.iter_balances().rev().copied().collect::<Vec<_>>(),
+        vec![1, 5, 10]
+    );
+}
+
+#[test]
+fn mutable_iterator() {
+    let mut account = account();
+    account
+        .iter_balances_mut()
+        .for_each(|balance| *balance *= 2);
+    assert_eq!(
+        [account.free, account.reserved, account.frozen],
+        [20, 10, 2]
+    );
+    assert_eq!(account.owner, "alice");
+}
+
+#[test]
+fn arrays() {
+    let mut account = account();
+    let balances: [&u64; <Account as NumFields<BalanceFields>>::NUM_FIELDS] =
+        account.as_balances_array();
+    assert_eq!(balances, [&10, &5, &1]);
+
+    let [free, reserved, _] = account.as_balances_array_mut();
+    std::mem::swap(free, reserved);
+    assert_eq!((account.free, account.reserved), (5, 10));
+}
+
+#[test]
+fn generic_struct() {
+    let mut pair = Pair {
+        name: "pair",
+        left: 'a',
+        right: 'b',
+    };
+    pair.iter_values_mut()
+        .for_each(|c| *c = c.to_ascii_uppercase());
+    assert_eq!(pair.iter_values().collect::<String>(), "AB");
+    assert_eq!(pair.as_values_array(), [&'A', &'B']);
+    assert_eq!(pair.name, "pair");
+}
@@ -0,0 +1,59 @@
+use metastruct::{metastruct, FromMapError};
+use std::collections::BTreeMap;
+
+#[metastruct(maps(counters(only_types(u64)), labels(only_types(String))))]
+#[derive(Debug, Default, PartialEq)]
+pub struct Metrics {
+    pub requests: u64,
+    pub errors: u64,
+    pub service: String,
+    #[metastruct(default = 1)]
+    pub version: u32,
+    pub retries: u64,
+}
+
+#[test]
+fn to_map() {
+    let metrics = Metrics {
+        requests: 10,
+        errors: 2,
+        service: "api".to_string(),
+        version: 3,
+        retries: 1,
+    };
+    assert_eq!(
+        metrics.to_counters(),
+        BTreeMap::from([("errors", 2), ("requests", 10), ("retries", 1)])
+    );
+    assert_eq!(
+        metrics.to_labels(),
+        BTreeMap::from([("service", "api".to_string())])
+    );
+}
+
+#[test]
+fn round_trip() {
+    let metrics = Metrics {
+        requests: 10,
+        errors: 2,
+        retries: 1,
+        version: 1,
+        ..Metrics::default()
+    };
+    assert_eq!(
+        Metrics::try_from_counters(metrics.to_counters()),
+        Ok(metrics)
+    );
+}
+
+#[test]
+fn missing_fields() {
+    let map = BTreeMap::from([("requests", 1), ("unknown", 5)]);
+    assert_eq!(
+        Metrics::try_from_counters(map),
+        Err(FromMapError {
+            missing: vec!["errors".to_string(), "retries".to_string()],
+            invalid: vec![],
+        })
+    );
+}
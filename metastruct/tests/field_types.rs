@@ -0,0 +1,84 @@
+use metastruct::{metastruct, selectors::AllFields, FieldTypes};
+use std::fmt::Debug;
+
+#[metastruct(num_fields(
+    all(field_types),
+    numeric(selector = "NumericFields", only_types(u64, u8), field_types)
+))]
+pub struct Foo {
+    pub a: u64,
+    pub b: String,
+    pub c: u8,
+}
+
+#[metastruct(num_fields(all(field_types), shallow(selector = "ShallowFields", field_types)))]
+pub struct Outer {
+    pub x: bool,
+    #[metastruct(flatten(all = NumericFields))]
+    pub foo: Foo,
+}
+
+/// Require `Copy` of every selected field type with a single bound.
+fn all_copy<T, S>() -> bool
+where
+    T: FieldTypes<S>,
+    T::Types: Copy,
+{
+    true
+}
+
+fn debug_default<T, S>() -> String
+where
+    T: FieldTypes<S>,
+    T::Types: Debug + Default,
+{
+    format!("{:?}", T::Types::default())
+}
+
+// The struct is never constructed, and only used for its `NumFields` implementation.
+#[allow(dead_code)]
+mod private {
+    use metastruct::{metastruct, selectors::AllFields, NumFields};
+
+    struct Private;
+
+    // Without `field_types`, private field types don't appear in the public implementations.
+    #[metastruct(num_fields(all()))]
+    pub struct Public {
+        x: Private,
+        pub y: u64,
+    }
+
+    #[test]
+    fn private_field_type() {
+        assert_eq!(<Public as NumFields<AllFields>>::NUM_FIELDS, 2);
+    }
+}
+
+#[test]
+fn tuple_of_types() {
+    let numeric: <Foo as FieldTypes<NumericFields>>::Types = (1u64, 2u8);
+    let all: <Foo as FieldTypes<AllFields>>::Types = (1u64, String::new(), 2u8);
+    assert_eq!(numeric.0 + all.0, 2);
+}
+
+#[test]
+fn bounds_on_all_types() {
+    assert!(all_copy::<Foo, NumericFields>());
+    assert_eq!(debug_default::<Foo, AllFields>(), "(0, \"\", 0)");
+}
+
+#[test]
+fn flattened_types() {
+    let types: <Outer as FieldTypes<AllFields>>::Types = (true, (1u64, 2u8));
+    let shallow: <Outer as FieldTypes<ShallowFields>>::Types = (
+        false,
+        Foo {
+            a: 0,
+            b: String::new(),
+            c: 0,
+        },
+    );
+    assert!(types.0 && !shallow.0);
+    assert!(all_copy::<Outer, AllFields>());
+}
@@ -0,0 +1,116 @@
+use metastruct::{metastruct, selectors::AllFields, NumFields};
+
+#[metastruct(
+    mappings(map_committee_leaves(field_names), map_committee_leaves_mut(mutable),),
+    num_fields(all())
+)]
+#[derive(Debug, Default, PartialEq)]
+pub struct Committees {
+    current: u64,
+    next: u64,
+}
+
+#[metastruct(
+    mappings(
+        map_state_leaves(field_names),
+        map_state_leaves_mut(mutable),
+        map_state_fields(),
+    ),
+    num_fields(all(), shallow(selector = "ShallowFields"))
+)]
+#[derive(Debug, Default, PartialEq)]
+pub struct State {
+    slot: u64,
+    #[metastruct(flatten(
+        map_state_leaves = map_committee_leaves,
+        map_state_leaves_mut = map_committee_leaves_mut,
+        all = AllFields
+    ))]
+    committees: Committees,
+    balance: u64,
+}
+
+#[metastruct(mappings(map_outer_leaves(field_names)), num_fields(all()))]
+pub struct Outer {
+    #[metastruct(flatten(map_outer_leaves = map_state_leaves, all = AllFields))]
+    state: State,
+    extra: u64,
+}
+
+fn state() -> State {
+    State {
+        slot: 1,
+        committees: Committees {
+            current: 2,
+            next: 3,
+        },
+        balance: 4,
+    }
+}
+
+#[test]
+fn flattened_names_and_indices() {
+    let mut visited = vec![];
+    map_state_leaves!(&state(), |i, name, x| visited.push((i, name, *x)));
+    assert_eq!(
+        visited,
+        vec![
+            (0, "slot", 1),
+            (1, "committees.current", 2),
+            (2, "committees.next", 3),
+            (3, "balance", 4),
+        ]
+    );
+}
+
+#[test]
+fn flattened_mutable() {
+    let mut state = state();
+    map_state_leaves_mut!(&mut state, |i, x| *x += 10 * i as u64);
+    assert_eq!(
+        state,
+        State {
+            slot: 1,
+            committees: Committees {
+                current: 12,
+                next: 23,
+            },
+            balance: 34,
+        }
+    );
+}
+
+#[test]
+fn flatten_only_applies_to_named_mappings() {
+    let mut count = 0;
+    map_state_fields!(&state(), |_, _| count += 1);
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn nested_flattening() {
+    let outer = Outer {
+        state: state(),
+        extra: 5,
+    };
+    let mut visited = vec![];
+    map_outer_leaves!(&outer, |i, name, x| visited.push((i, name, *x)));
+    assert_eq!(
+        visited,
+        vec![
+            (0, "state.slot", 1),
+            (1, "state.committees.current", 2),
+            (2, "state.committees.next", 3),
+            (3, "state.balance", 4),
+            (4, "extra", 5),
+        ]
+    );
+}
+
+#[test]
+fn flattened_num_fields() {
+    assert_eq!(<Committees as NumFields<AllFields>>::NUM_FIELDS, 2);
+    assert_eq!(<State as NumFields<AllFields>>::NUM_FIELDS, 4);
+    assert_eq!(<State as NumFields<ShallowFields>>::NUM_FIELDS, 3);
+    assert_eq!(<Outer as NumFields<AllFields>>::NUM_FIELDS, 5);
+}
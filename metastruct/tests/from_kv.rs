@@ -0,0 +1,97 @@
+use metastruct::{metastruct, FromMapError, InvalidEntry};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+#[metastruct(from_kv(prefix = "APP_", exclude(cache)))]
+#[derive(Debug, PartialEq)]
+pub struct Config {
+    pub host: Ipv4Addr,
+    #[metastruct(default = 8080)]
+    pub port: u16,
+    pub max_peers: usize,
+    #[metastruct(default = "info".to_string())]
+    pub log_level: String,
+    pub cache: Vec<u8>,
+}
+
+fn map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+    entries
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn from_map_with_defaults() {
+    let config = Config::from_map(&map(&[("APP_HOST", "127.0.0.1"), ("APP_MAX_PEERS", "50")]));
+    assert_eq!(
+        config,
+        Ok(Config {
+            host: Ipv4Addr::LOCALHOST,
+            port: 8080,
+            max_peers: 50,
+            log_level: "info".to_string(),
+            cache: vec![],
+        })
+    );
+}
+
+#[test]
+fn from_map_overrides_defaults() {
+    let config = Config::from_map(&map(&[
+        ("APP_HOST", "10.0.0.1"),
+        ("APP_PORT", "9000"),
+        ("APP_MAX_PEERS", "1"),
+        ("APP_LOG_LEVEL", "debug"),
+        ("APP_CACHE", "ignored"),
+    ]))
+    .unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.log_level, "debug");
+    assert!(config.cache.is_empty());
+}
+
+#[test]
+fn all_errors_reported() {
+    let err = Config::from_map(&map(&[("APP_PORT", "http"), ("APP_MAX_PEERS", "-1")])).unwrap_err();
+    assert_eq!(
+        err,
+        FromMapError {
+            missing: vec!["APP_HOST".to_string()],
+            invalid: vec![
+                InvalidEntry {
+                    key: "APP_PORT".to_string(),
+                    value: "http".to_string(),
+                    error: "invalid digit found in string".to_string(),
+                },
+                InvalidEntry {
+                    key: "APP_MAX_PEERS".to_string(),
+                    value: "-1".to_string(),
+                    error: "invalid digit found in string".to_string(),
+                },
+            ],
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "missing keys: `APP_HOST`; invalid values: `APP_PORT` = `http` (invalid digit found in \
+         string), `APP_MAX_PEERS` = `-1` (invalid digit found in string)"
+    );
+}
+
+#[metastruct(from_kv)]
+#[derive(Debug)]
+pub struct Unprefixed {
+    pub metastruct_test_from_env_value: u64,
+}
+
+#[test]
+fn from_env() {
+    std::env::set_var("METASTRUCT_TEST_FROM_ENV_VALUE", "42");
+    assert_eq!(
+        Unprefixed::from_env()
+            .unwrap()
+            .metastruct_test_from_env_value,
+        42
+    );
+}
@@ -0,0 +1,92 @@
+use metastruct::{metastruct, Field};
+use std::collections::HashMap;
+
+#[metastruct(lenses)]
+#[derive(Debug, Default)]
+pub struct Config {
+    pub port: u16,
+    pub host: String,
+    retries: u32,
+}
+
+#[metastruct(lenses(module = pair_lenses, exclude(label)))]
+pub struct Pair<T> {
+    pub label: &'static str,
+    pub left: T,
+    pub right: T,
+}
+
+/// Setter generic over the field being set, which records the fields that changed.
+fn set<F: Field<Config>>(config: &mut Config, value: F::Value, changed: &mut Vec<&'static str>)
+where
+    F::Value: PartialEq,
+{
+    if *F::get(config) != value {
+        *F::get_mut(config) = value;
+        changed.push(F::NAME);
+    }
+}
+
+#[test]
+fn generic_setter() {
+    let mut config = Config::default();
+    let mut changed = vec![];
+    set::<config_fields::Port>(&mut config, 8080, &mut changed);
+    set::<config_fields::Host>(&mut config, String::new(), &mut changed);
+    set::<config_fields::Retries>(&mut config, 3, &mut changed);
+    assert_eq!(changed, vec!["port", "retries"]);
+    assert_eq!((config.port, config.retries), (8080, 3));
+}
+
+#[test]
+fn names_and_indices() {
+    assert_eq!(<config_fields::Host as Field<Config>>::NAME, "host");
+    assert_eq!(<config_fields::Host as Field<Config>>::INDEX, 1);
+    // Indices are positions in the struct, even when some fields aren't selected.
+    assert_eq!(<pair_lenses::Right as Field<Pair<u8>>>::INDEX, 2);
+}
+
+#[test]
+fn per_field_metrics() {
+    fn record<F: Field<Pair<u32>, Value = u32>>(
+        pair: &Pair<u32>,
+        metrics: &mut HashMap<usize, u32>,
+    ) {
+        *metrics.entry(F::INDEX).or_default() += F::get(pair);
+    }
+
+    let pair = Pair {
+        label: "pair",
+        left: 1,
+        right: 2,
+    };
+    let mut metrics = HashMap::new();
+    record::<pair_lenses::Left>(&pair, &mut metrics);
+    record::<pair_lenses::Right>(&pair, &mut metrics);
+    record::<pair_lenses::Right>(&pair, &mut metrics);
+    assert_eq!(metrics, HashMap::from([(1, 1), (2, 4)]));
+    assert_eq!(pair.label, "pair");
+}
+
+mod nested {
+    use metastruct::metastruct;
+
+    #[metastruct(lenses)]
+    pub struct Inner {
+        pub(super) visible: u8,
+        hidden: u8,
+    }
+
+    pub fn hidden_index() -> usize {
+        <inner_fields::Hidden as metastruct::Field<Inner>>::INDEX
+    }
+}
+
+#[test]
+fn restricted_visibility() {
+    assert_eq!(
+        <nested::inner_fields::Visible as Field<nested::Inner>>::NAME,
+        "visible"
+    );
+    assert_eq!(nested::hidden_index(), 1);
+}
@@ -0,0 +1,181 @@
+use metastruct::{metastruct, selectors::AllFields, NumFields, NumFieldsWithContext};
+
+pub struct Config {
+    pub version: u8,
+    pub extra: bool,
+}
+
+fn is_v2(config: &Config) -> bool {
+    config.version >= 2
+}
+
+fn has_extra(config: &Config) -> bool {
+    config.extra
+}
+
+#[metastruct(
+    mappings(
+        map_fields(),
+        map_fields_mut(mutable),
+        map_versioned(groups(versioned))
+    ),
+    num_fields(conditional(context = "Config"), numeric(selector = "NumericFields"))
+)]
+#[derive(Debug, PartialEq)]
+pub struct Foo {
+    a: u64,
+    #[metastruct(only_if(versioned = is_v2))]
+    b: u64,
+    #[metastruct(only_if(
+        map_fields = has_extra,
+        map_fields_mut = has_extra,
+        conditional = has_extra
+    ))]
+    c: u64,
+}
+
+fn foo() -> Foo {
+    Foo {
+        a: 1,
+        b: 10,
+        c: 100,
+    }
+}
+
+#[test]
+fn mapping_with_context() {
+    let config = Config {
+        version: 1,
+        extra: false,
+    };
+    let mut visited = vec![];
+    map_fields!(&foo(), config, |i, x| visited.push((i, *x)));
+    assert_eq!(visited, vec![(0, 1), (1, 10)]);
+
+    let config = Config {
+        version: 1,
+        extra: true,
+    };
+    let mut visited = vec![];
+    map_fields!(&foo(), config, |i, x| visited.push((i, *x)));
+    assert_eq!(visited, vec![(0, 1), (1, 10), (2, 100)]);
+}
+
+#[test]
+fn mutable_mapping_with_context() {
+    let config = Config {
+        version: 1,
+        extra: false,
+    };
+    let mut foo = foo();
+    map_fields_mut!(&mut foo, &config, |_, x| *x += 1);
+    assert_eq!(
+        foo,
+        Foo {
+            a: 2,
+            b: 11,
+            c: 100
+        }
+    );
+}
+
+#[test]
+fn group_predicate() {
+    let mut total = 0;
+    let config = Config {
+        version: 2,
+        extra: false,
+    };
+    map_versioned!(&foo(), config, |_, x| total += *x);
+    assert_eq!(total, 111);
+
+    let mut total = 0;
+    let config = Config {
+        version: 1,
+        extra: false,
+    };
+    map_versioned!(&foo(), config, |_, x| total += *x);
+    assert_eq!(total, 101);
+}
+
+#[test]
+fn runtime_num_fields() {
+    assert_eq!(<Foo as NumFields<AllFields>>::NUM_FIELDS, 3);
+    assert_eq!(<Foo as NumFields<NumericFields>>::NUM_FIELDS, 3);
+
+    let config = Config {
+        version: 1,
+        extra: false,
+    };
+    assert_eq!(
+        <Foo as NumFieldsWithContext<AllFields, Config>>::num_fields(&config),
+        2
+    );
+    let config = Config {
+        version: 1,
+        extra: true,
+    };
+    assert_eq!(
+        <Foo as NumFieldsWithContext<AllFields, Config>>::num_fields(&config),
+        3
+    );
+}
+
+#[metastruct(mappings(map_inner_fields(field_names)))]
+pub struct Inner {
+    x: u64,
+    #[metastruct(only_if(map_inner_fields = has_extra))]
+    y: u64,
+}
+
+#[metastruct(mappings(map_outer_fields(field_names)))]
+pub struct Outer {
+    a: u64,
+    #[metastruct(only_if(map_outer_fields = is_v2))]
+    skipped: u64,
+    #[metastruct(
+        flatten(map_outer_fields = map_inner_fields),
+        only_if(map_outer_fields = has_extra)
+    )]
+    inner: Inner,
+    b: u64,
+}
+
+fn outer() -> Outer {
+    Outer {
+        a: 1,
+        skipped: 2,
+        inner: Inner { x: 3, y: 4 },
+        b: 5,
+    }
+}
+
+#[test]
+fn skipped_flattened_field_indices() {
+    let config = Config {
+        version: 1,
+        extra: false,
+    };
+    let mut visited = vec![];
+    map_outer_fields!(&outer(), config, |i, name, _| visited.push((i, name)));
+    assert_eq!(visited, vec![(0, "a"), (4, "b")]);
+}
+
+#[test]
+fn flattened_context() {
+    let config = Config {
+        version: 1,
+        extra: true,
+    };
+    let mut visited = vec![];
+    map_outer_fields!(&outer(), config, |i, name, x| visited.push((i, name, *x)));
+    assert_eq!(
+        visited,
+        vec![
+            (0, "a", 1),
+            (2, "inner.x", 3),
+            (3, "inner.y", 4),
+            (4, "b", 5)
+        ]
+    );
+}
@@ -0,0 +1,97 @@
+//! Generated code must not depend on prelude names, which may be shadowed where it's expanded.
+#![allow(dead_code)]
+
+use metastruct::metastruct;
+
+type Result<T> = std::result::Result<T, Error>;
+struct Error;
+struct Ok;
+struct Err;
+struct Some;
+struct None;
+struct String;
+struct Option;
+
+#[metastruct(
+    field_enum,
+    string_access,
+    from_kv(prefix = "APP_"),
+    maps(numbers(only_types(u64))),
+    dyn_access(values()),
+    dispatch(map_config_at()),
+    mappings(try_map_config(fallible, method = "try_for_each"))
+)]
+pub struct Config {
+    pub a: u64,
+    #[metastruct(default = 2)]
+    pub b: u64,
+}
+
+#[test]
+fn field_enum() {
+    assert!("a".parse::<ConfigField>().is_ok());
+    assert!(ConfigField::try_from(2).is_err());
+}
+
+#[test]
+fn string_access() {
+    let mut config = Config { a: 1, b: 2 };
+    assert!(config.set_field_from_str("a", "3").is_ok());
+    assert!(config.set_field_from_str("c", "3").is_err());
+    assert_eq!(
+        config.field_to_string("a").ok().as_deref(),
+        std::option::Option::Some("3")
+    );
+}
+
+#[test]
+fn from_kv() {
+    let map = [("APP_A".to_string(), "1".to_string())]
+        .into_iter()
+        .collect();
+    let config = Config::from_map(&map).ok().unwrap();
+    assert_eq!((config.a, config.b), (1, 2));
+}
+
+#[test]
+fn field_map() {
+    let config = Config { a: 1, b: 2 };
+    let map = config.to_numbers();
+    assert!(Config::try_from_numbers(map).is_ok());
+}
+
+#[test]
+fn dyn_access() {
+    let mut config = Config { a: 1, b: 2 };
+    assert!(config.get_values("a").is_some());
+    assert!(config.get_values_by_index_mut(2).is_none());
+}
+
+#[test]
+fn dispatch() {
+    let config = Config { a: 1, b: 2 };
+    assert_eq!(
+        map_config_at!(&config, 1, |x| *x),
+        std::option::Option::Some(2)
+    );
+    assert!(map_config_at!(&config, 2, |x| *x).is_none());
+}
+
+#[test]
+fn fallible_method() {
+    let config = Config { a: 1, b: 2 };
+    let mut sum = 0;
+    let result = config.try_for_each(|_, x| {
+        sum += *x;
+        if *x > 1 {
+            std::result::Result::Err(Error)
+        } else {
+            std::result::Result::Ok(())
+        }
+    });
+    assert!(result.is_err());
+    assert_eq!(sum, 3);
+    assert!(config
+        .try_for_each(|_, _| std::result::Result::<(), Error>::Ok(()))
+        .is_ok());
+}
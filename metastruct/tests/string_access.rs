@@ -0,0 +1,60 @@
+use metastruct::{metastruct, SetFieldError, UnknownFieldError};
+
+#[metastruct(string_access(groups(tunable)))]
+#[derive(Debug, Default)]
+pub struct Params {
+    #[metastruct(groups(tunable))]
+    pub max_peers: usize,
+    #[metastruct(groups(tunable))]
+    pub ratio: f64,
+    #[metastruct(groups(tunable))]
+    pub name: String,
+    pub internal: Vec<u8>,
+}
+
+#[test]
+fn set_and_get() {
+    let mut params = Params::default();
+    for (name, value) in [("max_peers", "50"), ("ratio", "0.5"), ("name", "node")] {
+        params.set_field_from_str(name, value).unwrap();
+    }
+    assert_eq!(params.max_peers, 50);
+    assert_eq!(params.ratio, 0.5);
+    assert_eq!(params.name, "node");
+    assert_eq!(params.field_to_string("max_peers").unwrap(), "50");
+    assert_eq!(params.field_to_string("ratio").unwrap(), "0.5");
+}
+
+#[test]
+fn invalid_value() {
+    let mut params = Params::default();
+    let err = params.set_field_from_str("max_peers", "-1").unwrap_err();
+    assert_eq!(
+        err,
+        SetFieldError::InvalidValue {
+            name: "max_peers",
+            value: "-1".to_string(),
+            error: "invalid digit found in string".to_string(),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value `-1` for field `max_peers`: invalid digit found in string"
+    );
+    assert_eq!(params.max_peers, 0);
+}
+
+#[test]
+fn unknown_field() {
+    let mut params = Params::default();
+    let unknown = UnknownFieldError {
+        name: "internal".to_string(),
+        valid_names: &["max_peers", "ratio", "name"],
+    };
+    assert_eq!(
+        params.set_field_from_str("internal", "x"),
+        Err(SetFieldError::UnknownField(unknown.clone()))
+    );
+    assert_eq!(params.field_to_string("internal"), Err(unknown));
+    assert!(params.internal.is_empty());
+}
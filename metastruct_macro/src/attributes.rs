@@ -1,6 +1,11 @@
 //! Utilities to help with parsing configuration attributes.
 use darling::{export::NestedMeta, Error, FromMeta};
-use syn::Ident;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream, Parser},
+    punctuated::Punctuated,
+    token, Ident, Meta, Token, Type, TypeParamBound, Visibility,
+};
 
 /// List of identifiers implementing `FromMeta`.
 ///
@@ -33,3 +38,188 @@ impl FromMeta for IdentList {
         Ok(Self { idents })
     }
 }
+
+/// List of attribute patterns implementing `FromMeta`, e.g. `exclude_attrs(serde(skip), doc)`.
+#[derive(Debug)]
+pub struct AttrPatternList {
+    pub patterns: Vec<Meta>,
+}
+
+impl FromMeta for AttrPatternList {
+    fn from_list(items: &[NestedMeta]) -> Result<Self, Error> {
+        let patterns = items
+            .iter()
+            .map(|nested_meta| match nested_meta {
+                NestedMeta::Meta(m) => Ok(m.clone()),
+                NestedMeta::Lit(l) => Err(Error::custom(format!(
+                    "expected attribute pattern, got literal: {:?}",
+                    l
+                ))),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+}
+
+/// List of types implementing `FromMeta`, e.g. `only_types(u64, Vec<u8>)`.
+#[derive(Debug)]
+pub struct TypeList {
+    pub types: Vec<Type>,
+}
+
+impl FromMeta for TypeList {
+    fn from_meta(item: &Meta) -> Result<Self, Error> {
+        let types = item
+            .require_list()?
+            .parse_args_with(Punctuated::<Type, Token![,]>::parse_terminated)?
+            .into_iter()
+            .collect();
+        Ok(Self { types })
+    }
+}
+
+/// Visibility parsed from a string, e.g. `visibility = "pub(crate)"`.
+///
+/// The string `"private"` denotes the inherited (private) visibility.
+#[derive(Debug)]
+pub struct VisibilityFilter {
+    pub vis: Visibility,
+}
+
+impl FromMeta for VisibilityFilter {
+    fn from_string(value: &str) -> Result<Self, Error> {
+        let vis = if value == "private" {
+            Visibility::Inherited
+        } else {
+            syn::parse_str(value)?
+        };
+        Ok(Self { vis })
+    }
+}
+
+/// Trait bounds, parsed from a string like `"Debug + Send"`.
+#[derive(Debug)]
+pub struct TraitBounds {
+    pub bounds: Punctuated<TypeParamBound, Token![+]>,
+}
+
+impl FromMeta for TraitBounds {
+    fn from_string(value: &str) -> Result<Self, Error> {
+        let bounds = Punctuated::parse_separated_nonempty.parse_str(value)?;
+        Ok(Self { bounds })
+    }
+}
+
+/// Set expression over named groups of fields.
+///
+/// Expressions are built from group names, the binary operators `+` (union), `-` (difference)
+/// and `&` (intersection), the functions `union(..)`, `intersection(..)` and `difference(..)`,
+/// and parentheses. Binary operators all have the same precedence and associate to the left.
+///
+/// A comma-separated list of expressions denotes their intersection, so the plain list
+/// `groups(a, b)` selects the fields that are in both `a` and `b`.
+///
+/// The group name `all` is reserved and contains every field.
+#[derive(Debug)]
+pub enum GroupExpr {
+    Group(Ident),
+    Union(Vec<GroupExpr>),
+    Intersection(Vec<GroupExpr>),
+    Difference(Box<GroupExpr>, Box<GroupExpr>),
+}
+
+impl GroupExpr {
+    /// Evaluate the expression for a single field, given a predicate for group membership.
+    pub fn contains(&self, in_group: &impl Fn(&Ident) -> bool) -> bool {
+        match self {
+            Self::Group(group) => in_group(group),
+            Self::Union(exprs) => exprs.iter().any(|expr| expr.contains(in_group)),
+            Self::Intersection(exprs) => exprs.iter().all(|expr| expr.contains(in_group)),
+            Self::Difference(lhs, rhs) => lhs.contains(in_group) && !rhs.contains(in_group),
+        }
+    }
+
+    /// All group names mentioned in the expression.
+    pub fn groups(&self) -> Vec<&Ident> {
+        match self {
+            Self::Group(group) => vec![group],
+            Self::Union(exprs) | Self::Intersection(exprs) => {
+                exprs.iter().flat_map(Self::groups).collect()
+            }
+            Self::Difference(lhs, rhs) => lhs.groups().into_iter().chain(rhs.groups()).collect(),
+        }
+    }
+
+    /// Parse a comma-separated list of expressions as their intersection.
+    fn parse_list(input: ParseStream) -> syn::Result<Self> {
+        let exprs = Punctuated::<Self, Token![,]>::parse_terminated(input)?;
+        Ok(Self::Intersection(exprs.into_iter().collect()))
+    }
+
+    fn parse_operand(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            return Self::parse_list(&content);
+        }
+
+        let ident: Ident = input.parse()?;
+        if !input.peek(token::Paren) {
+            return Ok(Self::Group(ident));
+        }
+
+        let content;
+        parenthesized!(content in input);
+        let args = Punctuated::<Self, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        match ident.to_string().as_str() {
+            "union" => Ok(Self::Union(args)),
+            "intersection" => Ok(Self::Intersection(args)),
+            "difference" => {
+                let mut args = args.into_iter();
+                let first = args.next().ok_or_else(|| {
+                    syn::Error::new(ident.span(), "difference requires at least one argument")
+                })?;
+                Ok(args.fold(first, |lhs, rhs| {
+                    Self::Difference(Box::new(lhs), Box::new(rhs))
+                }))
+            }
+            other => Err(syn::Error::new(
+                ident.span(),
+                format!(
+                    "unknown group function `{other}`, expected one of \
+                     `union`, `intersection` or `difference`"
+                ),
+            )),
+        }
+    }
+}
+
+impl Parse for GroupExpr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut expr = Self::parse_operand(input)?;
+        loop {
+            expr = if input.peek(Token![+]) {
+                input.parse::<Token![+]>()?;
+                Self::Union(vec![expr, Self::parse_operand(input)?])
+            } else if input.peek(Token![-]) {
+                input.parse::<Token![-]>()?;
+                Self::Difference(Box::new(expr), Box::new(Self::parse_operand(input)?))
+            } else if input.peek(Token![&]) {
+                input.parse::<Token![&]>()?;
+                Self::Intersection(vec![expr, Self::parse_operand(input)?])
+            } else {
+                return Ok(expr);
+            };
+        }
+    }
+}
+
+impl FromMeta for GroupExpr {
+    fn from_meta(item: &Meta) -> Result<Self, Error> {
+        let list = item.require_list()?;
+        list.parse_args_with(Self::parse_list).map_err(Error::from)
+    }
+}
@@ -0,0 +1,46 @@
+//! Static assertions that field types implement traits, reported at the field definitions.
+use crate::{
+    attributes::TraitBounds,
+    exclude::{calculate_selected_field_opts, calculate_selected_fields},
+    FieldOpts, SelectionOpts,
+};
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{spanned::Spanned, Ident, ItemStruct, Type};
+
+/// Assert that every field selected by `selection` satisfies `bounds`.
+pub(crate) fn generate_bound_assertions(
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    selection: &SelectionOpts,
+    trait_bounds: &TraitBounds,
+) -> TokenStream {
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let bounds = &trait_bounds.bounds;
+
+    // Each call is spanned to the field's type, so that errors point at the struct definition
+    // rather than into the generated code.
+    let assertions = calculate_selected_fields(selection, fields, field_opts)
+        .into_iter()
+        .zip(calculate_selected_field_opts(selection, fields, field_opts))
+        .map(|((_, field_type), field_opts)| {
+            let cfg = field_opts.cfg.attr();
+            quote_spanned! {field_type.span()=>
+                #cfg
+                __metastruct_assert_bounds::<#field_type>();
+            }
+        });
+
+    quote! {
+        const _: () = {
+            #[allow(dead_code)]
+            fn __metastruct_assert_bounds<T: ?Sized + #bounds>() {}
+
+            #[allow(dead_code)]
+            fn __metastruct_assert_field_bounds #impl_generics () #where_clause {
+                #(#assertions)*
+            }
+        };
+    }
+}
@@ -0,0 +1,335 @@
+//! Support for `cfg` and `cfg_attr` on fields.
+//!
+//! Attribute macros see the fields of a struct before any `cfg` or `cfg_attr` attributes are
+//! evaluated. We can't simply copy these attributes into the generated mapping macros either,
+//! because those are expanded in the crate that *invokes* them, where a predicate like
+//! `feature = "x"` would refer to the wrong crate's features.
+//!
+//! Instead, we generate mapping macros once for each combination of the predicates used by the
+//! struct's fields, and gate each copy with a `cfg` that is evaluated where the struct is
+//! defined. Structs without any conditional fields have a single, ungated, combination. As the
+//! number of combinations grows exponentially, only a few distinct predicates are supported.
+//!
+//! All other items are expanded where the struct is defined, and are generated once. Each field
+//! has an entry for each combination of the predicates of its `cfg_attr` attributes, gated by
+//! those and by its own `cfg` predicates, and generated items carry the gate of each entry
+//! wherever they mention its field.
+use crate::is_attr_with_ident;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Attribute, File, Ident, Meta, Token,
+};
+
+/// Parsed `#[cfg_attr(predicate, attrs..)]` attribute.
+pub(crate) struct CfgAttr {
+    pub predicate: Meta,
+    pub attrs: Vec<Meta>,
+}
+
+impl Parse for CfgAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let predicate = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let attrs = Punctuated::<Meta, Token![,]>::parse_terminated(input)?
+            .into_iter()
+            .collect();
+        Ok(Self { predicate, attrs })
+    }
+}
+
+/// Parse all the `cfg_attr` attributes in `attrs`.
+fn cfg_attrs(attrs: &[Attribute]) -> impl Iterator<Item = CfgAttr> + '_ {
+    attrs
+        .iter()
+        .filter(|attr| is_attr_with_ident(attr, "cfg_attr"))
+        .filter_map(|attr| attr.parse_args::<CfgAttr>().ok())
+}
+
+/// Predicates of the `cfg` attributes in `attrs`.
+pub(crate) fn cfg_predicates(attrs: &[Attribute]) -> Vec<Meta> {
+    attrs
+        .iter()
+        .filter(|attr| is_attr_with_ident(attr, "cfg"))
+        .filter_map(|attr| attr.parse_args::<Meta>().ok())
+        .collect()
+}
+
+/// Conditional `metastruct` attributes applied via `cfg_attr`, paired with their predicates.
+pub(crate) fn metastruct_cfg_attrs(attrs: &[Attribute]) -> Vec<(Meta, Meta)> {
+    cfg_attrs(attrs)
+        .flat_map(|cfg_attr| {
+            let predicate = cfg_attr.predicate;
+            cfg_attr
+                .attrs
+                .into_iter()
+                .filter(|meta| meta.path().is_ident("metastruct"))
+                .map(move |meta| (predicate.clone(), meta))
+        })
+        .collect()
+}
+
+/// Remove any `metastruct` attributes from a `cfg_attr` attribute.
+///
+/// Returns `None` if nothing would remain, and other attributes unmodified.
+pub(crate) fn discard_conditional_metastruct_attrs(attr: &Attribute) -> Option<Attribute> {
+    if !is_attr_with_ident(attr, "cfg_attr") {
+        return Some(attr.clone());
+    }
+    let Ok(CfgAttr { predicate, attrs }) = attr.parse_args::<CfgAttr>() else {
+        return Some(attr.clone());
+    };
+    let attrs = attrs
+        .into_iter()
+        .filter(|meta| !meta.path().is_ident("metastruct"))
+        .collect::<Vec<_>>();
+    if attrs.is_empty() {
+        return None;
+    }
+    let mut attr = attr.clone();
+    attr.meta = syn::parse_quote! { cfg_attr(#predicate, #(#attrs),*) };
+    Some(attr)
+}
+
+/// Maximum number of distinct predicates, which generate up to 2^`MAX_PREDICATES` copies of mapping
+/// macros.
+pub(crate) const MAX_PREDICATES: usize = 6;
+
+/// Conjunction of `cfg` predicates, each of which must either hold or not hold.
+///
+/// Gates are used both for the configurations in which a field exists with particular options,
+/// and for the combinations of predicates that mapping macros are generated for.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CfgGate {
+    terms: Vec<(Meta, bool)>,
+}
+
+impl CfgGate {
+    /// Gate requiring all of `predicates` to hold.
+    pub fn all(predicates: Vec<Meta>) -> Self {
+        Self {
+            terms: predicates
+                .into_iter()
+                .map(|predicate| (predicate, true))
+                .collect(),
+        }
+    }
+
+    /// Whether `predicate` is required to hold.
+    pub fn is_enabled(&self, predicate: &Meta) -> bool {
+        self.terms
+            .iter()
+            .find(|(p, _)| same_predicate(p, predicate))
+            .is_some_and(|(_, enabled)| *enabled)
+    }
+
+    /// Whether this gate holds in `variant`, which must determine all of the gate's predicates.
+    pub fn holds_in(&self, variant: &CfgGate) -> bool {
+        self.terms
+            .iter()
+            .all(|(predicate, enabled)| variant.is_enabled(predicate) == *enabled)
+    }
+
+    /// Whether this gate holds in every configuration.
+    pub fn is_unconditional(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Gate requiring both this gate and `other` to hold, or `None` if they never both hold.
+    pub fn and(&self, other: &CfgGate) -> Option<CfgGate> {
+        let mut terms = self.terms.clone();
+        for (predicate, enabled) in &other.terms {
+            match terms.iter().find(|(p, _)| same_predicate(p, predicate)) {
+                Some((_, e)) if e != enabled => return None,
+                Some(_) => {}
+                None => terms.push((predicate.clone(), *enabled)),
+            }
+        }
+        Some(Self { terms })
+    }
+
+    /// The predicates that this gate depends on.
+    pub fn predicates(&self) -> impl Iterator<Item = &Meta> {
+        self.terms.iter().map(|(predicate, _)| predicate)
+    }
+
+    /// The `cfg` predicate of this gate, or `None` if it always holds.
+    pub fn predicate(&self) -> Option<TokenStream> {
+        let terms = self
+            .terms
+            .iter()
+            .map(|(predicate, enabled)| {
+                if *enabled {
+                    quote! { #predicate }
+                } else {
+                    quote! { not(#predicate) }
+                }
+            })
+            .collect::<Vec<_>>();
+        match terms.as_slice() {
+            [] => None,
+            [term] => Some(term.clone()),
+            terms => Some(quote! { all(#(#terms),*) }),
+        }
+    }
+
+    /// `#[cfg]` attribute applying this gate to a field, statement, expression or item.
+    pub fn attr(&self) -> Option<TokenStream> {
+        self.predicate()
+            .map(|predicate| quote! { #[cfg(#predicate)] })
+    }
+}
+
+/// Remove duplicate predicates, preserving order.
+pub(crate) fn unique_predicates(predicates: impl IntoIterator<Item = Meta>) -> Vec<Meta> {
+    let mut unique = Vec::<Meta>::new();
+    for predicate in predicates {
+        if !unique.iter().any(|p| same_predicate(p, &predicate)) {
+            unique.push(predicate);
+        }
+    }
+    unique
+}
+
+/// Enumerate every combination of truth values for a list of unique `predicates`.
+pub(crate) fn cfg_variants(predicates: &[Meta]) -> Vec<CfgGate> {
+    let n = predicates.len();
+    (0..1usize << n)
+        .map(|mask| CfgGate {
+            terms: predicates
+                .iter()
+                .enumerate()
+                .map(|(i, predicate)| (predicate.clone(), mask & (1 << i) != 0))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Generate items once for each combination of the predicates of `gates`, gating each copy.
+///
+/// This is for items that can't carry a `cfg` on each field, like tuple types. `generate` is
+/// called with each combination, in which every one of the `gates` either holds or doesn't.
+pub(crate) fn generate_for_each_variant(
+    gates: &[&CfgGate],
+    mut generate: impl FnMut(&CfgGate) -> TokenStream,
+) -> TokenStream {
+    let predicates = unique_predicates(gates.iter().flat_map(|gate| gate.predicates()).cloned());
+    cfg_variants(&predicates)
+        .iter()
+        .map(|variant| cfg_gated(generate(variant), variant.predicate().as_ref()))
+        .collect()
+}
+
+/// Expression counting `counts` for the entries whose gates hold.
+///
+/// The expression is constant if the counts are, and is a plain sum if no gate is conditional.
+pub(crate) fn cfg_count<'a>(
+    counts: impl IntoIterator<Item = (&'a CfgGate, TokenStream)>,
+) -> TokenStream {
+    let (gates, counts): (Vec<_>, Vec<_>) = counts.into_iter().unzip();
+    if gates.iter().all(|gate| gate.is_unconditional()) {
+        return quote! { 0 #(+ #counts)* };
+    }
+    let attrs = gates.iter().map(|gate| gate.attr());
+    quote! {{
+        let __metastruct_n: usize = 0;
+        #(
+            #attrs
+            let __metastruct_n = __metastruct_n + #counts;
+        )*
+        __metastruct_n
+    }}
+}
+
+/// Position of each entry among the entries whose gates hold.
+///
+/// Positions are literals up to the first conditional entry, and constant expressions after it.
+pub(crate) fn cfg_positions(gates: &[&CfgGate]) -> Vec<TokenStream> {
+    (0..gates.len())
+        .map(|i| {
+            if gates[..i].iter().all(|gate| gate.is_unconditional()) {
+                quote! { #i }
+            } else {
+                cfg_count(gates[..i].iter().map(|gate| (*gate, quote! { 1 })))
+            }
+        })
+        .collect()
+}
+
+/// Patterns matching the position of each entry among the entries whose gates hold.
+///
+/// Positions which aren't literals are matched by binding `ident` and comparing it in a guard.
+pub(crate) fn cfg_position_patterns(gates: &[&CfgGate], ident: &Ident) -> Vec<TokenStream> {
+    cfg_positions(gates)
+        .into_iter()
+        .enumerate()
+        .map(|(i, position)| {
+            if gates[..i].iter().all(|gate| gate.is_unconditional()) {
+                position
+            } else {
+                quote! { #ident if #ident == #position }
+            }
+        })
+        .collect()
+}
+
+/// Report each error in the configurations in which any of its gates hold, and gate `items` to the
+/// remaining configurations.
+///
+/// This is for errors caused by the options of entries which only exist in some configurations,
+/// which are reported as if the items were generated for each configuration separately.
+pub(crate) fn cfg_errors(
+    errors: Vec<(Vec<&CfgGate>, darling::Error)>,
+    items: TokenStream,
+) -> TokenStream {
+    // Errors which apply in every configuration are reported alone.
+    let (unconditional, conditional): (Vec<_>, Vec<_>) = errors
+        .into_iter()
+        .filter(|(gates, _)| !gates.is_empty())
+        .partition(|(gates, _)| gates.iter().any(|gate| gate.is_unconditional()));
+    if !unconditional.is_empty() {
+        return darling::Error::multiple(unconditional.into_iter().map(|(_, err)| err).collect())
+            .write_errors();
+    }
+    if conditional.is_empty() {
+        return items;
+    }
+
+    let mut predicates = vec![];
+    let mut output = vec![];
+    for (gates, err) in conditional {
+        let gate_predicates = gates.iter().filter_map(|gate| gate.predicate());
+        let predicate = quote! { any(#(#gate_predicates),*) };
+        output.push(cfg_gated(err.write_errors(), Some(&predicate)));
+        predicates.push(predicate);
+    }
+    output.push(cfg_gated(
+        items,
+        Some(&quote! { not(any(#(#predicates),*)) }),
+    ));
+    output.into_iter().collect()
+}
+
+/// Gate every item in `items` behind `#[cfg(predicate)]`.
+pub(crate) fn cfg_gated(items: TokenStream, predicate: Option<&TokenStream>) -> TokenStream {
+    let Some(predicate) = predicate else {
+        return items;
+    };
+    let file = match syn::parse2::<File>(items) {
+        Ok(file) => file,
+        Err(err) => return err.to_compile_error(),
+    };
+    let items = file.items.iter();
+    quote! {
+        #(
+            #[cfg(#predicate)]
+            #items
+        )*
+    }
+}
+
+fn same_predicate(a: &Meta, b: &Meta) -> bool {
+    quote!(#a).to_string() == quote!(#b).to_string()
+}
@@ -0,0 +1,157 @@
+use crate::{
+    cfg::{cfg_position_patterns, CfgGate},
+    exclude::{calculate_selected_field_opts, calculate_selected_fields},
+    DynAccessOpts, FieldOpts, SelectionOpts,
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Ident, ItemStruct, Type};
+
+/// Gates of the fields selected by `selection`.
+fn selected_gates<'a>(
+    selection: &SelectionOpts,
+    fields: &[(Ident, Type)],
+    field_opts: &'a [FieldOpts],
+) -> Vec<&'a CfgGate> {
+    calculate_selected_field_opts(selection, fields, field_opts)
+        .into_iter()
+        .map(|field_opts| &field_opts.cfg)
+        .collect()
+}
+
+/// Type of the trait objects returned for `dyn_access_opts`.
+fn trait_object(dyn_access_opts: &DynAccessOpts) -> TokenStream {
+    match &dyn_access_opts.trait_bounds {
+        Some(trait_bounds) => {
+            let bounds = &trait_bounds.bounds;
+            quote! { dyn #bounds }
+        }
+        None => quote! { dyn ::core::any::Any },
+    }
+}
+
+pub(crate) fn generate_dyn_access(
+    key: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    dyn_access_opts: &DynAccessOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    let get = format_ident!("get_{key}");
+    let get_mut = format_ident!("get_{key}_mut");
+    let get_by_index = format_ident!("get_{key}_by_index");
+    let get_by_index_mut = format_ident!("get_{key}_by_index_mut");
+
+    let trait_object = trait_object(dyn_access_opts);
+
+    let selected_fields = calculate_selected_fields(&dyn_access_opts.selection, fields, field_opts)
+        .into_iter()
+        .map(|(field_name, _)| field_name)
+        .collect::<Vec<_>>();
+    let names = selected_fields
+        .iter()
+        .map(|field_name| field_name.to_string())
+        .collect::<Vec<_>>();
+    let gates = selected_gates(&dyn_access_opts.selection, fields, field_opts);
+    let cfgs = gates.iter().map(|gate| gate.attr()).collect::<Vec<_>>();
+    let indices = cfg_position_patterns(&gates, &format_ident!("index"));
+
+    let get_doc = format!("Get the `{key}` field with the given name.");
+    let get_mut_doc = format!("Get the `{key}` field with the given name, mutably.");
+    let get_by_index_doc =
+        format!("Get the `{key}` field at the given position, counting only the `{key}` fields.");
+    let get_by_index_mut_doc = format!(
+        "Get the `{key}` field at the given position, counting only the `{key}` fields, mutably."
+    );
+
+    quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #get_doc]
+            #vis fn #get(&self, name: &str) -> ::core::option::Option<&(#trait_object)> {
+                match name {
+                    #(#cfgs #names => ::core::option::Option::Some(&self.#selected_fields),)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            #[doc = #get_mut_doc]
+            #vis fn #get_mut(&mut self, name: &str) -> ::core::option::Option<&mut (#trait_object)> {
+                match name {
+                    #(#cfgs #names => ::core::option::Option::Some(&mut self.#selected_fields),)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            #[doc = #get_by_index_doc]
+            #vis fn #get_by_index(&self, index: usize) -> ::core::option::Option<&(#trait_object)> {
+                match index {
+                    #(#cfgs #indices => ::core::option::Option::Some(&self.#selected_fields),)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            #[doc = #get_by_index_mut_doc]
+            #vis fn #get_by_index_mut(&mut self, index: usize) -> ::core::option::Option<&mut (#trait_object)> {
+                match index {
+                    #(#cfgs #indices => ::core::option::Option::Some(&mut self.#selected_fields),)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn generate_dyn_iter(
+    key: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    dyn_iter_opts: &DynAccessOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let iter_mut = format_ident!("{key}_mut");
+    let trait_object = trait_object(dyn_iter_opts);
+
+    let selected_fields = calculate_selected_fields(&dyn_iter_opts.selection, fields, field_opts)
+        .into_iter()
+        .map(|(field_name, _)| field_name)
+        .collect::<Vec<_>>();
+    let names = selected_fields
+        .iter()
+        .map(|field_name| field_name.to_string())
+        .collect::<Vec<_>>();
+    let cfgs = selected_gates(&dyn_iter_opts.selection, fields, field_opts)
+        .into_iter()
+        .map(|gate| gate.attr())
+        .collect::<Vec<_>>();
+
+    let iter_doc = format!("Iterate over the names and values of the `{key}` fields.");
+    let iter_mut_doc = format!("Iterate mutably over the names and values of the `{key}` fields.");
+
+    quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #iter_doc]
+            #vis fn #key(
+                &self,
+            ) -> impl ::core::iter::ExactSizeIterator<Item = (&'static str, &(#trait_object))>
+                   + ::core::iter::DoubleEndedIterator {
+                [#(#cfgs (#names, &self.#selected_fields as &(#trait_object))),*].into_iter()
+            }
+
+            #[doc = #iter_mut_doc]
+            #vis fn #iter_mut(
+                &mut self,
+            ) -> impl ::core::iter::ExactSizeIterator<Item = (&'static str, &mut (#trait_object))>
+                   + ::core::iter::DoubleEndedIterator {
+                [#(#cfgs (#names, &mut self.#selected_fields as &mut (#trait_object))),*]
+                    .into_iter()
+            }
+        }
+    }
+}
@@ -1,37 +1,245 @@
-use crate::{attributes::IdentList, FieldOpts};
-use syn::{Ident, Type};
+use crate::{is_attr_with_ident, FieldOpts, SelectionOpts};
+use darling::export::NestedMeta;
+use quote::ToTokens;
+use std::collections::HashSet;
+use syn::{Attribute, Ident, Meta, Path, Type};
 
-pub(crate) fn calculate_excluded_fields<'a>(
-    item_excludes: &'a Option<IdentList>,
-    item_groups: &Option<IdentList>,
+/// Name of the implicit group containing every field.
+const ALL_GROUP: &str = "all";
+
+/// Check that every field named by `include` in `selection` is one of the struct's `fields`.
+///
+/// This is checked against all of the struct's fields, as fields disabled by `cfg` may be
+/// included by selections which are also used when the fields are enabled.
+pub(crate) fn check_included_fields(
+    selection: &SelectionOpts,
+    fields: &[&Ident],
+) -> Result<(), darling::Error> {
+    let Some(include) = &selection.include else {
+        return Ok(());
+    };
+    let errors = include
+        .idents
+        .iter()
+        .filter(|ident| !fields.contains(ident))
+        .map(|ident| {
+            darling::Error::custom(format!("included field `{ident}` does not exist"))
+                .with_span(ident)
+        })
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(darling::Error::multiple(errors))
+    }
+}
+
+/// Check that no field has an `only_if` predicate keyed by the implicit group of every field.
+///
+/// Predicates keyed by a group apply to every mapping selecting that group, so a predicate keyed
+/// by `all` would silently apply to any mapping mentioning `all` in its group expression.
+pub(crate) fn check_predicate_keys(field_opts: &[FieldOpts]) -> Result<(), darling::Error> {
+    let errors = field_opts
+        .iter()
+        .flat_map(|field_opts| field_opts.only_if.keys())
+        .filter(|key| *key == ALL_GROUP)
+        .map(|key| {
+            darling::Error::custom(format!(
+                "`{ALL_GROUP}` is the group of every field, and can't be used as an `only_if` \
+                 key; give the mapping or `num_fields` entry another name"
+            ))
+            .with_span(key)
+        })
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(darling::Error::multiple(errors))
+    }
+}
+
+/// Calculate whether each of the `fields` is selected by `selection`.
+///
+/// This is the single source of truth for field selection, and every generator should use it
+/// (or `calculate_selected_fields`) rather than inspecting the selection options directly.
+///
+/// Fields are matched by position rather than by name, as a field may have several entries with
+/// different options, each existing in different configurations.
+pub(crate) fn calculate_selection(
+    selection: &SelectionOpts,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+) -> Vec<bool> {
+    // Groups which have at least one member declared via `#[metastruct(groups(..))]`.
+    //
+    // Membership of these groups is opt-in, whereas membership of all other groups is opt-out
+    // via `#[metastruct(exclude_from(..))]`.
+    let declared_groups = field_opts
+        .iter()
+        .filter_map(|opts| opts.groups.as_ref())
+        .flat_map(|groups| &groups.idents)
+        .collect::<HashSet<_>>();
+
+    fields
+        .iter()
+        .zip(field_opts)
+        .map(|((field_name, field_type), field_opts)| {
+            let included = selection
+                .include
+                .as_ref()
+                .is_none_or(|include| include.idents.contains(field_name));
+            let excluded = field_opts.exclude
+                || selection
+                    .exclude
+                    .as_ref()
+                    .is_some_and(|exclude| exclude.idents.contains(field_name));
+            let in_groups = selection.groups.as_ref().is_none_or(|groups| {
+                groups.contains(&|group| is_in_group(group, field_opts, &declared_groups))
+            });
+            let type_selected =
+                selection.only_types.as_ref().is_none_or(|only_types| {
+                    only_types
+                        .types
+                        .iter()
+                        .any(|ty| same_tokens(ty, field_type))
+                }) && !selection.except_types.as_ref().is_some_and(|except_types| {
+                    except_types
+                        .types
+                        .iter()
+                        .any(|ty| same_tokens(ty, field_type))
+                });
+            let vis_selected = selection
+                .visibility
+                .as_ref()
+                .is_none_or(|filter| same_tokens(&filter.vis, &field_opts.vis));
+            let excluded_by_attr = selection.exclude_attrs.as_ref().is_some_and(|patterns| {
+                patterns.patterns.iter().any(|pattern| {
+                    field_opts
+                        .attrs
+                        .iter()
+                        .any(|attr| attr_matches_pattern(attr, pattern))
+                })
+            });
+            included && !excluded && !excluded_by_attr && in_groups && type_selected && vis_selected
+        })
+        .collect()
+}
+
+/// Calculate the fields selected by `selection`, in declaration order.
+pub(crate) fn calculate_selected_fields<'a>(
+    selection: &SelectionOpts,
     fields: &'a [(Ident, Type)],
     field_opts: &[FieldOpts],
-) -> Vec<&'a Ident> {
-    item_excludes
+) -> Vec<&'a (Ident, Type)> {
+    fields
+        .iter()
+        .zip(calculate_selection(selection, fields, field_opts))
+        .filter_map(|(field, selected)| selected.then_some(field))
+        .collect()
+}
+
+/// Calculate the options of each of the fields selected by `selection`, in declaration order.
+pub(crate) fn calculate_selected_field_opts<'a>(
+    selection: &SelectionOpts,
+    fields: &[(Ident, Type)],
+    field_opts: &'a [FieldOpts],
+) -> Vec<&'a FieldOpts> {
+    field_opts
+        .iter()
+        .zip(calculate_selection(selection, fields, field_opts))
+        .filter_map(|(field_opts, selected)| selected.then_some(field_opts))
+        .collect()
+}
+
+/// Calculate the runtime predicates that apply to each of the fields selected by `selection`.
+///
+/// A field's `only_if` predicate applies if its key is `name` (the name of the mapping or
+/// `num_fields` entry), or one of the groups mentioned by `selection`. A field is only visited
+/// if *all* of its applicable predicates hold.
+pub(crate) fn calculate_field_predicates<'a>(
+    name: &Ident,
+    selection: &SelectionOpts,
+    fields: &[(Ident, Type)],
+    field_opts: &'a [FieldOpts],
+) -> Vec<Vec<&'a Path>> {
+    let groups = selection
+        .groups
         .as_ref()
+        .map(|groups| groups.groups())
+        .unwrap_or_default();
+
+    calculate_selected_field_opts(selection, fields, field_opts)
         .into_iter()
-        .map(|exclude| &exclude.idents)
-        .flatten()
-        .chain(
-            fields
+        .map(|field_opts| {
+            field_opts
+                .only_if
                 .iter()
-                .zip(field_opts)
-                .filter_map(|((field_name, _), field_opts)| {
-                    let excluded_from_all = field_opts.exclude;
-                    let excluded_from_any_group = item_groups
-                        .as_ref()
-                        .and_then(|groups| {
-                            let excluded_groups = field_opts.exclude_from.as_ref()?;
-                            Some(
-                                groups
-                                    .idents
-                                    .iter()
-                                    .any(|group| excluded_groups.idents.contains(group)),
-                            )
-                        })
-                        .unwrap_or(false);
-                    (excluded_from_all || excluded_from_any_group).then_some(field_name)
-                }),
-        )
+                .filter(|(key, _)| *key == name || groups.contains(key))
+                .map(|(_, predicate)| predicate)
+                .collect()
+        })
         .collect()
 }
+
+fn is_in_group(group: &Ident, field_opts: &FieldOpts, declared_groups: &HashSet<&Ident>) -> bool {
+    if group == ALL_GROUP {
+        return true;
+    }
+    let excluded_from_group = field_opts
+        .exclude_from
+        .as_ref()
+        .is_some_and(|excluded_groups| excluded_groups.idents.contains(group));
+    let declared_member = field_opts
+        .groups
+        .as_ref()
+        .is_some_and(|groups| groups.idents.contains(group));
+
+    (declared_member || !declared_groups.contains(group)) && !excluded_from_group
+}
+
+/// Syntactic equality of two token trees, ignoring spans.
+fn same_tokens(a: &impl ToTokens, b: &impl ToTokens) -> bool {
+    a.to_token_stream().to_string() == b.to_token_stream().to_string()
+}
+
+/// Check whether `attr` matches an `exclude_attrs` pattern.
+///
+/// The attribute's path must match the pattern's path. If the pattern has a list of nested
+/// metas, then each of them must also be present in the attribute's list. Nested paths in the
+/// pattern match any nested meta with the same path, e.g. `serde(default)` matches both
+/// `#[serde(default)]` and `#[serde(default = "f")]`.
+fn attr_matches_pattern(attr: &Attribute, pattern: &Meta) -> bool {
+    let path_matches = match pattern.path().get_ident() {
+        Some(ident) => is_attr_with_ident(attr, &ident.to_string()),
+        None => same_tokens(attr.path(), pattern.path()),
+    };
+    if !path_matches {
+        return false;
+    }
+
+    match pattern {
+        Meta::Path(_) => true,
+        Meta::NameValue(_) => same_tokens(&attr.meta, pattern),
+        Meta::List(pattern_list) => {
+            let Ok(attr_list) = attr.meta.require_list() else {
+                return false;
+            };
+            let (Ok(pattern_items), Ok(attr_items)) = (
+                NestedMeta::parse_meta_list(pattern_list.tokens.clone()),
+                NestedMeta::parse_meta_list(attr_list.tokens.clone()),
+            ) else {
+                return false;
+            };
+            pattern_items.iter().all(|pattern_item| {
+                attr_items
+                    .iter()
+                    .any(|attr_item| match (pattern_item, attr_item) {
+                        (NestedMeta::Meta(Meta::Path(path)), NestedMeta::Meta(meta)) => {
+                            same_tokens(path, meta.path())
+                        }
+                        _ => same_tokens(pattern_item, attr_item),
+                    })
+            })
+        }
+    }
+}
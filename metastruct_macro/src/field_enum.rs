@@ -0,0 +1,131 @@
+use crate::{
+    cfg::{cfg_count, cfg_position_patterns, cfg_positions},
+    exclude::{calculate_selected_field_opts, calculate_selected_fields},
+    names::upper_camel_case,
+    FieldEnumOpts, FieldOpts,
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Ident, ItemStruct, Type};
+
+/// Name of the enum naming the fields of `type_name`.
+pub(crate) fn field_enum_name(type_name: &Ident) -> Ident {
+    format_ident!("{type_name}Field")
+}
+
+/// Name of the variant of the field enum for `field_name`.
+pub(crate) fn field_variant_name(field_name: &Ident) -> Ident {
+    format_ident!("{}", upper_camel_case(field_name))
+}
+
+pub(crate) fn generate_field_enum(
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    field_enum_opts: &FieldEnumOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let enum_name = field_enum_name(type_name);
+
+    let selected_fields = calculate_selected_fields(&field_enum_opts.selection, fields, field_opts)
+        .into_iter()
+        .map(|(field_name, _)| field_name)
+        .collect::<Vec<_>>();
+    let gates = calculate_selected_field_opts(&field_enum_opts.selection, fields, field_opts)
+        .into_iter()
+        .map(|field_opts| &field_opts.cfg)
+        .collect::<Vec<_>>();
+    let cfgs = gates.iter().map(|gate| gate.attr()).collect::<Vec<_>>();
+    let num_fields = cfg_count(gates.iter().map(|gate| (*gate, quote! { 1 })));
+    let variants = selected_fields
+        .iter()
+        .map(|field_name| field_variant_name(field_name))
+        .collect::<Vec<_>>();
+    let names = selected_fields
+        .iter()
+        .map(|field_name| field_name.to_string())
+        .collect::<Vec<_>>();
+    let indices = cfg_positions(&gates);
+    let index_patterns = cfg_position_patterns(&gates, &format_ident!("index"));
+
+    let doc = format!("Names of the fields of [`{type_name}`].");
+    let variant_docs = names.iter().map(|name| format!("The `{name}` field."));
+
+    quote! {
+        #[doc = #doc]
+        #[derive(
+            ::core::fmt::Debug,
+            ::core::clone::Clone,
+            ::core::marker::Copy,
+            ::core::cmp::PartialEq,
+            ::core::cmp::Eq,
+            ::core::cmp::PartialOrd,
+            ::core::cmp::Ord,
+            ::core::hash::Hash,
+        )]
+        #vis enum #enum_name {
+            #(
+                #cfgs
+                #[doc = #variant_docs]
+                #variants,
+            )*
+        }
+
+        impl #enum_name {
+            /// All fields, in the order they are defined.
+            pub const ALL: [Self; #num_fields] = [#(#cfgs Self::#variants),*];
+
+            /// Names of all fields, in the order they are defined.
+            pub const NAMES: [&'static str; #num_fields] = [#(#cfgs #names),*];
+
+            /// Name of the field, as written in the struct definition.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    #(#cfgs Self::#variants => #names,)*
+                }
+            }
+
+            /// Position of the field in `ALL`.
+            pub const fn index(self) -> usize {
+                match self {
+                    #(#cfgs Self::#variants => #indices,)*
+                }
+            }
+        }
+
+        impl ::core::fmt::Display for #enum_name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+
+        impl ::core::str::FromStr for #enum_name {
+            type Err = metastruct::UnknownFieldError;
+
+            fn from_str(name: &str) -> ::core::result::Result<Self, Self::Err> {
+                match name {
+                    #(#cfgs #names => ::core::result::Result::Ok(Self::#variants),)*
+                    _ => ::core::result::Result::Err(metastruct::UnknownFieldError {
+                        name: name.to_string(),
+                        valid_names: &Self::NAMES,
+                    }),
+                }
+            }
+        }
+
+        impl ::core::convert::TryFrom<usize> for #enum_name {
+            type Error = metastruct::FieldIndexError;
+
+            fn try_from(index: usize) -> ::core::result::Result<Self, Self::Error> {
+                match index {
+                    #(#cfgs #index_patterns => ::core::result::Result::Ok(Self::#variants),)*
+                    _ => ::core::result::Result::Err(metastruct::FieldIndexError {
+                        index,
+                        num_fields: #num_fields,
+                    }),
+                }
+            }
+        }
+    }
+}
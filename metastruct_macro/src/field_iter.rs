@@ -0,0 +1,90 @@
+use crate::{
+    cfg::cfg_count,
+    exclude::{calculate_selected_field_opts, calculate_selected_fields},
+    homogeneous::common_field_type,
+    FieldIterOpts, FieldOpts,
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Ident, ItemStruct, Type};
+
+pub(crate) fn generate_field_iter(
+    key: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    field_iter_opts: &FieldIterOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let iter = format_ident!("iter_{key}");
+    let iter_mut = format_ident!("iter_{key}_mut");
+    let as_array = format_ident!("as_{key}_array");
+    let as_array_mut = format_ident!("as_{key}_array_mut");
+
+    let selected_fields = calculate_selected_fields(&field_iter_opts.selection, fields, field_opts);
+    let selected_field_opts =
+        calculate_selected_field_opts(&field_iter_opts.selection, fields, field_opts);
+    let (value_type, assertions) =
+        match common_field_type(key, &selected_fields, &selected_field_opts) {
+            Ok(common) => common,
+            Err(err) => return err,
+        };
+    let selected_field_names = selected_fields
+        .iter()
+        .map(|(field_name, _)| field_name)
+        .collect::<Vec<_>>();
+    let cfgs = selected_field_opts
+        .iter()
+        .map(|field_opts| field_opts.cfg.attr())
+        .collect::<Vec<_>>();
+
+    // Tie the length of the array to a `NumFields` implementation, if one is given.
+    let len = match &field_iter_opts.selector {
+        Some(selector) => quote! {
+            <#type_name #ty_generics as metastruct::NumFields<#selector>>::NUM_FIELDS
+        },
+        None => cfg_count(
+            selected_field_opts
+                .iter()
+                .map(|field_opts| (&field_opts.cfg, quote! { 1 })),
+        ),
+    };
+
+    let iter_doc = format!("Iterate over the `{key}` fields.");
+    let iter_mut_doc = format!("Iterate mutably over the `{key}` fields.");
+    let as_array_doc = format!("Array of references to the `{key}` fields.");
+    let as_array_mut_doc = format!("Array of mutable references to the `{key}` fields.");
+
+    quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #iter_doc]
+            #vis fn #iter(
+                &self,
+            ) -> impl ::core::iter::ExactSizeIterator<Item = &#value_type>
+                   + ::core::iter::DoubleEndedIterator {
+                self.#as_array().into_iter()
+            }
+
+            #[doc = #iter_mut_doc]
+            #vis fn #iter_mut(
+                &mut self,
+            ) -> impl ::core::iter::ExactSizeIterator<Item = &mut #value_type>
+                   + ::core::iter::DoubleEndedIterator {
+                self.#as_array_mut().into_iter()
+            }
+
+            #[doc = #as_array_doc]
+            #vis fn #as_array(&self) -> [&#value_type; #len] {
+                #assertions
+                [#(#cfgs &self.#selected_field_names),*]
+            }
+
+            #[doc = #as_array_mut_doc]
+            #vis fn #as_array_mut(&mut self) -> [&mut #value_type; #len] {
+                [#(#cfgs &mut self.#selected_field_names),*]
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    exclude::{calculate_selected_field_opts, calculate_selected_fields, calculate_selection},
+    homogeneous::common_field_type,
+    FieldMapOpts, FieldOpts,
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Ident, ItemStruct, Type};
+
+pub(crate) fn generate_field_map(
+    key: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    field_map_opts: &FieldMapOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let to_map = format_ident!("to_{key}");
+    let try_from_map = format_ident!("try_from_{key}");
+
+    let selected_fields = calculate_selected_fields(&field_map_opts.selection, fields, field_opts);
+    let selected_field_opts =
+        calculate_selected_field_opts(&field_map_opts.selection, fields, field_opts);
+    let (value_type, assertions) =
+        match common_field_type(key, &selected_fields, &selected_field_opts) {
+            Ok(common) => common,
+            Err(err) => return err,
+        };
+    let selected_field_names = selected_fields
+        .iter()
+        .map(|(field_name, _)| field_name)
+        .collect::<Vec<_>>();
+    let names = selected_field_names
+        .iter()
+        .map(|field_name| field_name.to_string())
+        .collect::<Vec<_>>();
+    let selected_cfgs = selected_field_opts
+        .iter()
+        .map(|field_opts| field_opts.cfg.attr())
+        .collect::<Vec<_>>();
+
+    // Fields which aren't selected take their default value.
+    let field_names = fields
+        .iter()
+        .map(|(field_name, _)| field_name)
+        .collect::<Vec<_>>();
+    let cfgs = field_opts
+        .iter()
+        .map(|field_opts| field_opts.cfg.attr())
+        .collect::<Vec<_>>();
+    let field_values = fields
+        .iter()
+        .zip(field_opts)
+        .zip(calculate_selection(
+            &field_map_opts.selection,
+            fields,
+            field_opts,
+        ))
+        .map(|(((field_name, _), field_opts), selected)| {
+            if selected {
+                let name = field_name.to_string();
+                quote! {
+                    __metastruct_map.remove(#name).or_else(|| {
+                        __metastruct_error.missing.push(#name.to_string());
+                        ::core::option::Option::None
+                    })
+                }
+            } else if let Some(default) = &field_opts.default {
+                quote! { ::core::option::Option::Some(#default) }
+            } else {
+                quote! { ::core::option::Option::Some(::core::default::Default::default()) }
+            }
+        });
+
+    let to_map_doc = format!("Clone the `{key}` fields into a map from field names to values.");
+    let try_from_map_doc = format!(
+        "Construct [`{type_name}`] from a map from the names of the `{key}` fields to their \
+         values.\n\nEntries for other fields are ignored, and fields which aren't `{key}` fields \
+         take their default values."
+    );
+
+    quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #to_map_doc]
+            #vis fn #to_map(&self) -> ::std::collections::BTreeMap<&'static str, #value_type> {
+                #assertions
+                ::std::collections::BTreeMap::from([
+                    #(
+                        #selected_cfgs
+                        (#names, ::core::clone::Clone::clone(&self.#selected_field_names)),
+                    )*
+                ])
+            }
+
+            #[doc = #try_from_map_doc]
+            #vis fn #try_from_map(
+                mut __metastruct_map: ::std::collections::BTreeMap<&str, #value_type>,
+            ) -> ::core::result::Result<Self, metastruct::FromMapError> {
+                let mut __metastruct_error = metastruct::FromMapError::default();
+                #(
+                    #cfgs
+                    let #field_names = #field_values;
+                )*
+                #(
+                    #cfgs
+                    let ::core::option::Option::Some(#field_names) = #field_names else {
+                        return ::core::result::Result::Err(__metastruct_error);
+                    };
+                )*
+                ::core::result::Result::Ok(Self { #(#cfgs #field_names,)* })
+            }
+        }
+    }
+}
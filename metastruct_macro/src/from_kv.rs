@@ -0,0 +1,119 @@
+use crate::{exclude::calculate_selection, FieldOpts, FromKvOpts};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Ident, ItemStruct, Type};
+
+pub(crate) fn generate_from_kv(
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    from_kv_opts: &FromKvOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let prefix = from_kv_opts.prefix.as_deref().unwrap_or_default();
+
+    // Each field is bound to an `Option` which is `None` if an error was recorded for it.
+    let field_names = fields
+        .iter()
+        .map(|(field_name, _)| field_name)
+        .collect::<Vec<_>>();
+    let cfgs = field_opts
+        .iter()
+        .map(|field_opts| field_opts.cfg.attr())
+        .collect::<Vec<_>>();
+    let field_values = fields
+        .iter()
+        .zip(field_opts)
+        .zip(calculate_selection(
+            &from_kv_opts.selection,
+            fields,
+            field_opts,
+        ))
+        .map(|(((field_name, field_type), field_opts), selected)| {
+            let default = field_opts
+                .default
+                .as_ref()
+                .map(|default| quote! { #default });
+            if !selected {
+                let default =
+                    default.unwrap_or_else(|| quote! { ::core::default::Default::default() });
+                return quote! { ::core::option::Option::Some::<#field_type>(#default) };
+            }
+
+            let key = format!(
+                "{prefix}{}",
+                field_name
+                    .to_string()
+                    .trim_start_matches("r#")
+                    .to_uppercase()
+            );
+            let if_missing = match default {
+                Some(default) => quote! { ::core::option::Option::Some(#default) },
+                None => quote! {{
+                    __metastruct_error.missing.push(#key.to_string());
+                    ::core::option::Option::None
+                }},
+            };
+            quote! {
+                match __metastruct_lookup(#key) {
+                    ::core::option::Option::Some(__metastruct_value) => __metastruct_value
+                        .parse::<#field_type>()
+                        .map_err(|error| {
+                            __metastruct_error.invalid.push(metastruct::InvalidEntry {
+                                key: #key.to_string(),
+                                value: __metastruct_value.clone(),
+                                error: error.to_string(),
+                            })
+                        })
+                        .ok(),
+                    ::core::option::Option::None => #if_missing,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let from_map_doc = format!(
+        "Construct [`{type_name}`] from a map, with the value of each field under the key \
+         `{prefix}FIELD_NAME`."
+    );
+    let from_env_doc =
+        format!("Construct [`{type_name}`] from environment variables named `{prefix}FIELD_NAME`.");
+
+    quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #from_map_doc]
+            #vis fn from_map(
+                map: &::std::collections::HashMap<::std::string::String, ::std::string::String>,
+            ) -> ::core::result::Result<Self, metastruct::FromMapError> {
+                Self::__metastruct_from_kv(|key| map.get(key).cloned())
+            }
+
+            #[doc = #from_env_doc]
+            #vis fn from_env() -> ::core::result::Result<Self, metastruct::FromMapError> {
+                Self::__metastruct_from_kv(|key| ::std::env::var(key).ok())
+            }
+
+            fn __metastruct_from_kv(
+                mut __metastruct_lookup: impl FnMut(&str) -> ::core::option::Option<::std::string::String>,
+            ) -> ::core::result::Result<Self, metastruct::FromMapError> {
+                let mut __metastruct_error = metastruct::FromMapError::default();
+                #(
+                    #cfgs
+                    let #field_names = #field_values;
+                )*
+                if !__metastruct_error.is_empty() {
+                    return ::core::result::Result::Err(__metastruct_error);
+                }
+                #(
+                    #cfgs
+                    let ::core::option::Option::Some(#field_names) = #field_names else {
+                        return ::core::result::Result::Err(__metastruct_error);
+                    };
+                )*
+                ::core::result::Result::Ok(Self { #(#cfgs #field_names,)* })
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Support for generated items which require all selected fields to have the same type.
+use crate::FieldOpts;
+use darling::Error;
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+use syn::{spanned::Spanned, Ident, Type};
+
+/// The type shared by all of the `selected_fields` of the item `name`.
+///
+/// Also returns statements which fail to compile if any field has a different type, reporting
+/// the error at that field's type. Returns the error to report if no fields are selected.
+///
+/// The type of the first field which exists in every configuration is preferred, as the types of
+/// conditional fields may only exist in some configurations.
+pub(crate) fn common_field_type<'a>(
+    name: &Ident,
+    selected_fields: &[&'a (Ident, Type)],
+    selected_field_opts: &[&FieldOpts],
+) -> Result<(&'a Type, TokenStream), TokenStream> {
+    let common_field = selected_fields
+        .iter()
+        .zip(selected_field_opts)
+        .find(|(_, field_opts)| field_opts.cfg.is_unconditional())
+        .map(|(field, _)| field)
+        .or(selected_fields.first());
+    let Some((_, common_type)) = common_field else {
+        return Err(
+            Error::custom(format!("`{name}` requires at least one selected field"))
+                .with_span(name)
+                .write_errors(),
+        );
+    };
+    let assertions = selected_fields
+        .iter()
+        .zip(selected_field_opts)
+        .map(|((_, field_type), field_opts)| {
+            let cfg = field_opts.cfg.attr();
+            quote_spanned! {field_type.span()=>
+                #cfg
+                let _: ::core::marker::PhantomData<#common_type> =
+                    ::core::marker::PhantomData::<#field_type>;
+            }
+        })
+        .collect();
+    Ok((common_type, assertions))
+}
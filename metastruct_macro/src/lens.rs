@@ -0,0 +1,128 @@
+use crate::{
+    cfg::cfg_positions,
+    exclude::calculate_selection,
+    names::{snake_case, upper_camel_case},
+    FieldOpts, LensOpts,
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, Ident, ItemStruct, Type, VisRestricted, Visibility};
+
+/// Name of the module containing the field markers of `type_name`.
+pub(crate) fn lens_module_name(type_name: &Ident, lens_opts: &LensOpts) -> Ident {
+    lens_opts
+        .module
+        .clone()
+        .unwrap_or_else(|| format_ident!("{}_fields", snake_case(type_name)))
+}
+
+/// Name of the marker type for `field_name`.
+pub(crate) fn lens_marker_name(field_name: &Ident) -> Ident {
+    format_ident!("{}", upper_camel_case(field_name))
+}
+
+pub(crate) fn generate_lenses(
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    lens_opts: &LensOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let module_name = lens_module_name(type_name, lens_opts);
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    // The index is the field's position in the whole struct, not in the selection.
+    let indices = cfg_positions(
+        &field_opts
+            .iter()
+            .map(|field_opts| &field_opts.cfg)
+            .collect::<Vec<_>>(),
+    );
+    let selected_fields = fields
+        .iter()
+        .zip(field_opts)
+        .zip(indices)
+        .zip(calculate_selection(
+            &lens_opts.selection,
+            fields,
+            field_opts,
+        ))
+        .filter_map(|(field, selected)| selected.then_some(field));
+
+    let mut markers = vec![];
+    let mut impls = vec![];
+    for (((field_name, field_type), field_opts), index) in selected_fields {
+        let marker_name = lens_marker_name(field_name);
+        let marker_vis = nested_visibility(&field_opts.vis);
+        let field_name_str = field_name.to_string();
+        let doc = format!("Marker for the `{field_name_str}` field of [`super::{type_name}`].");
+        let cfg = field_opts.cfg.attr();
+
+        markers.push(quote! {
+            #cfg
+            #[doc = #doc]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+            #marker_vis struct #marker_name;
+        });
+        // Implemented outside the module, so that field types resolve as in the struct definition.
+        impls.push(quote! {
+            #cfg
+            impl #impl_generics metastruct::Field<#type_name #ty_generics>
+            for #module_name::#marker_name
+            #where_clause
+            {
+                type Value = #field_type;
+                const NAME: &'static str = #field_name_str;
+                const INDEX: usize = #index;
+
+                fn get(value: &#type_name #ty_generics) -> &Self::Value {
+                    &value.#field_name
+                }
+
+                fn get_mut(value: &mut #type_name #ty_generics) -> &mut Self::Value {
+                    &mut value.#field_name
+                }
+            }
+        });
+    }
+
+    let module_doc = format!("Markers for the fields of [`{type_name}`].");
+
+    quote! {
+        #[doc = #module_doc]
+        #vis mod #module_name {
+            #(#markers)*
+        }
+
+        #(#impls)*
+    }
+}
+
+/// Visibility inside a child module equivalent to `vis` in its parent.
+fn nested_visibility(vis: &Visibility) -> Visibility {
+    let Visibility::Restricted(VisRestricted { path, .. }) = vis else {
+        return match vis {
+            Visibility::Inherited => parse_quote!(pub(super)),
+            _ => vis.clone(),
+        };
+    };
+    if path
+        .segments
+        .first()
+        .is_some_and(|segment| segment.ident == "crate")
+    {
+        return vis.clone();
+    }
+    // Paths relative to the parent module start with `self` or `super`.
+    let segments = path
+        .segments
+        .iter()
+        .skip_while(|segment| segment.ident == "self")
+        .collect::<Vec<_>>();
+    if segments.is_empty() {
+        parse_quote!(pub(super))
+    } else {
+        parse_quote!(pub(in super::#(#segments)::*))
+    }
+}
@@ -1,26 +1,110 @@
-use attributes::IdentList;
+use attributes::{AttrPatternList, GroupExpr, IdentList, TraitBounds, TypeList, VisibilityFilter};
 use darling::{export::NestedMeta, FromMeta};
-use proc_macro::TokenStream;
+use proc_macro2::TokenStream;
 use quote::quote;
 use std::collections::HashMap;
-use std::iter::FromIterator;
-use syn::{parse_macro_input, Attribute, Ident, ItemStruct};
+use syn::{Attribute, Expr, Field, Ident, ItemStruct, Meta, Path, Type, Visibility};
 
 mod attributes;
+mod bounds;
+mod cfg;
+mod dyn_access;
 mod exclude;
+mod field_enum;
+mod field_iter;
+mod field_map;
+mod from_kv;
+mod homogeneous;
+mod lens;
 mod mapping;
+mod names;
 mod num_fields;
+mod string_access;
+mod tuple;
+mod view;
 
-#[derive(Debug, FromMeta)]
-struct MappingOpts {
+/// Options for selecting the subset of a struct's fields that a generated item applies to.
+///
+/// These options are flattened into the options of each kind of generated item, and are
+/// evaluated by `exclude::calculate_selection`.
+#[derive(Debug, Default, FromMeta)]
+struct SelectionOpts {
+    /// Select only the named fields.
+    #[darling(default)]
+    include: Option<IdentList>,
+    /// Do not select the named fields.
     #[darling(default)]
     exclude: Option<IdentList>,
+    /// Select only the fields matching this set expression over groups.
+    #[darling(default)]
+    groups: Option<GroupExpr>,
+    /// Select only fields with one of the listed types.
+    ///
+    /// Types are compared syntactically, so aliases and differently-qualified paths don't match.
+    #[darling(default)]
+    only_types: Option<TypeList>,
+    /// Do not select fields with any of the listed types.
+    #[darling(default)]
+    except_types: Option<TypeList>,
+    /// Select only fields with exactly this visibility.
+    #[darling(default)]
+    visibility: Option<VisibilityFilter>,
+    /// Do not select fields carrying an attribute matching any of these patterns.
+    ///
+    /// A pattern like `serde` matches any `#[serde(..)]` attribute, while `serde(skip)` only
+    /// matches `serde` attributes containing `skip`.
+    #[darling(default)]
+    exclude_attrs: Option<AttrPatternList>,
+}
+
+#[derive(Debug, FromMeta)]
+struct MappingOpts {
+    #[darling(flatten)]
+    selection: SelectionOpts,
     #[darling(default)]
     mutable: bool,
     #[darling(default)]
     fallible: bool,
+    /// Pass each field's name to the closure, between its index and its value.
+    ///
+    /// The names of fields within flattened fields are their full dotted paths.
     #[darling(default)]
-    groups: Option<IdentList>,
+    field_names: bool,
+    /// Pass each field's variant of the struct's field enum to the closure, instead of its index.
+    ///
+    /// The field enum must have a variant for each selected field, and be in scope wherever the
+    /// mapping is invoked. Flattened fields are not supported.
+    #[darling(default)]
+    field_enum: bool,
+    /// Assert that the type of every selected field satisfies these bounds, like `"Hash + Clone"`.
+    #[darling(default)]
+    assert_bounds: Option<TraitBounds>,
+    /// Call the closure through generic helper functions rather than as a `dyn FnMut`.
+    ///
+    /// The closure is monomorphised for each field type, so it can be inlined into the mapping.
+    /// As the closure isn't coerced to a trait object, this also avoids the coercion for each field
+    /// that mappings need if consecutive fields have different types.
+    #[darling(default)]
+    static_dispatch: bool,
+    /// Also generate an inherent method with this name, applying a closure to the selected fields.
+    ///
+    /// The method takes the same closure as the mapping, with each field passed as `&T` for the
+    /// fields' common type, or as a trait object if `method_trait` is given.
+    #[darling(default)]
+    method: Option<Ident>,
+    /// Pass fields to the closure of the `method` as trait objects with these bounds.
+    #[darling(default)]
+    method_trait: Option<TraitBounds>,
+}
+
+#[derive(Debug, FromMeta)]
+struct DispatchOpts {
+    #[darling(flatten)]
+    selection: SelectionOpts,
+    #[darling(default)]
+    mutable: bool,
+    #[darling(default)]
+    fallible: bool,
 }
 
 #[derive(Debug, FromMeta)]
@@ -35,24 +119,33 @@ struct BiMappingOpts {
     #[darling(default)]
     other_mutable: bool,
     #[darling(default)]
-    exclude: Option<IdentList>,
-    #[darling(default)]
     fallible: bool,
+    /// Assert that the type of every selected field of `Self` satisfies these bounds.
     #[darling(default)]
-    groups: Option<IdentList>,
+    assert_bounds: Option<TraitBounds>,
+    #[darling(flatten)]
+    selection: SelectionOpts,
 }
 
 #[derive(Debug, FromMeta)]
 struct NumFieldsOpts {
-    #[darling(default)]
-    exclude: Option<IdentList>,
     #[darling(default)]
     selector: Option<Ident>,
+    /// Context type passed to the `only_if` predicates of conditional fields.
+    ///
+    /// Required if any selected field has a predicate for this `num_fields` entry.
     #[darling(default)]
-    groups: Option<IdentList>,
+    context: Option<Type>,
+    /// Also implement `FieldTypes`, listing the types of the selected fields.
+    ///
+    /// Flattened fields must implement `FieldTypes` for their own selector.
+    #[darling(default)]
+    field_types: bool,
+    #[darling(flatten)]
+    selection: SelectionOpts,
 }
 
-#[derive(Debug, Default, FromMeta)]
+#[derive(Debug, FromMeta)]
 struct FieldOpts {
     /// Exclude this field from *all* mapping macros.
     #[darling(default)]
@@ -63,6 +156,143 @@ struct FieldOpts {
     // FIXME(sproul): we currently don't verify this
     #[darling(default)]
     exclude_from: Option<IdentList>,
+    /// Declare this field as a member of the named groups.
+    ///
+    /// Once any field declares itself a member of a group, membership of that group becomes
+    /// opt-in and fields which do not list it are excluded from it.
+    #[darling(default)]
+    groups: Option<IdentList>,
+    /// Only visit this field in the named mappings or groups if the predicate holds.
+    ///
+    /// The predicate is a path to a function taking a reference to a context value and returning
+    /// a `bool`. Mappings to which any predicate applies take the context as an extra argument.
+    ///
+    /// Keys may not be `all`, which is the group of every field.
+    #[darling(default)]
+    only_if: HashMap<Ident, Path>,
+    /// Recurse into this field's own mappings or `num_fields` implementations.
+    ///
+    /// Keys are the names of mappings or `num_fields` entries on this struct. For mappings, the
+    /// value is the name of the field type's mapping to recurse into, which must be in scope
+    /// wherever the outer mapping is invoked. For `num_fields`, the value is the selector type of
+    /// the field type's `NumFields` implementation to add to the count.
+    ///
+    /// Conditional fields of the inner mapping are evaluated with the outer mapping's context, so
+    /// the outer mapping must also have conditional fields, with the same context type.
+    ///
+    /// Dispatch macros apply their closure to a single field, and can't flatten fields.
+    #[darling(default)]
+    flatten: HashMap<Ident, Path>,
+    /// Value of this field when constructing the struct from a map without a value for it.
+    #[darling(default)]
+    default: Option<Expr>,
+    /// Visibility of the field, copied from the struct definition.
+    #[darling(skip, default = || Visibility::Inherited)]
+    vis: Visibility,
+    /// Non-metastruct attributes of the field, copied from the struct definition.
+    #[darling(skip)]
+    attrs: Vec<Attribute>,
+    /// Configurations in which the field exists with these options.
+    ///
+    /// Always holds for the fields that mapping macros are generated for, which are generated for
+    /// each configuration instead.
+    #[darling(skip)]
+    cfg: cfg::CfgGate,
+}
+
+#[derive(Debug, FromMeta)]
+struct ViewOpts {
+    #[darling(flatten)]
+    selection: SelectionOpts,
+    /// Also generate a struct owning the selected fields, for use in partitions.
+    #[darling(default)]
+    owned: bool,
+}
+
+#[derive(Debug, FromMeta)]
+struct SplitOpts {
+    /// Names of the views to split the struct into.
+    views: IdentList,
+    /// Views to borrow mutably. The remaining views are borrowed immutably.
+    ///
+    /// Fields selected by a mutable view must not be selected by any other view of the split.
+    #[darling(default)]
+    mutable: Option<IdentList>,
+}
+
+#[derive(Debug, FromMeta)]
+struct PartitionOpts {
+    /// Names of the owned views to move the struct's fields into.
+    ///
+    /// Every field must be selected by exactly one of the views.
+    views: IdentList,
+}
+
+#[derive(Debug, Default, FromMeta)]
+#[darling(from_word = || Ok(Self::default()))]
+struct LensOpts {
+    /// Name of the module containing the markers, `<type_name>_fields` by default.
+    #[darling(default)]
+    module: Option<Ident>,
+    #[darling(flatten)]
+    selection: SelectionOpts,
+}
+
+#[derive(Debug, Default, FromMeta)]
+#[darling(from_word = || Ok(Self::default()))]
+struct FieldEnumOpts {
+    #[darling(flatten)]
+    selection: SelectionOpts,
+}
+
+#[derive(Debug, FromMeta)]
+struct DynAccessOpts {
+    /// Bounds of the trait objects returned, like `"Debug + Send"`. Defaults to `Any`.
+    #[darling(default, rename = "trait")]
+    trait_bounds: Option<TraitBounds>,
+    #[darling(flatten)]
+    selection: SelectionOpts,
+}
+
+#[derive(Debug, Default, FromMeta)]
+#[darling(from_word = || Ok(Self::default()))]
+struct StringAccessOpts {
+    #[darling(flatten)]
+    selection: SelectionOpts,
+}
+
+#[derive(Debug, Default, FromMeta)]
+#[darling(from_word = || Ok(Self::default()))]
+struct FromKvOpts {
+    /// Prefix of the key of each field, which is followed by the field's name in upper case.
+    #[darling(default)]
+    prefix: Option<String>,
+    /// Fields to look up. Other fields take their `default` value, or `Default::default()`.
+    #[darling(flatten)]
+    selection: SelectionOpts,
+}
+
+#[derive(Debug, FromMeta)]
+struct FieldMapOpts {
+    #[darling(flatten)]
+    selection: SelectionOpts,
+}
+
+#[derive(Debug, FromMeta)]
+struct FieldIterOpts {
+    /// Selector of a `NumFields` implementation giving the length of the generated arrays.
+    ///
+    /// Not supported for generic structs.
+    #[darling(default)]
+    selector: Option<Path>,
+    #[darling(flatten)]
+    selection: SelectionOpts,
+}
+
+#[derive(Debug, FromMeta)]
+struct TupleOpts {
+    #[darling(flatten)]
+    selection: SelectionOpts,
 }
 
 /// Top-level configuration via the `metastruct` attribute.
@@ -72,62 +302,309 @@ struct StructOpts {
     mappings: HashMap<Ident, MappingOpts>,
     #[darling(default)]
     bimappings: HashMap<Ident, BiMappingOpts>,
-    // FIXME(sproul): the `Ident` is kind of useless here, consider writing a custom FromMeta
+    /// Macros applying a closure to the selected field at a runtime index, if there is one.
+    ///
+    /// If any selected field has `only_if` predicates for the macro or its groups, the macro takes
+    /// a context after the index, and returns `None` for the field if its predicates don't hold.
+    #[darling(default)]
+    dispatch: HashMap<Ident, DispatchOpts>,
+    /// The `Ident` is only used to match `only_if` predicates on conditional fields.
     #[darling(default)]
     num_fields: HashMap<Ident, NumFieldsOpts>,
+    /// Structs of references to, or optionally owning, a selection of fields. They are named after
+    /// the struct and the view.
+    #[darling(default)]
+    views: HashMap<Ident, ViewOpts>,
+    /// Methods borrowing several views at once, each of which may be mutable.
+    #[darling(default)]
+    splits: HashMap<Ident, SplitOpts>,
+    /// Methods moving the struct's fields into several owned views, and back again.
+    #[darling(default)]
+    partitions: HashMap<Ident, PartitionOpts>,
+    /// Generate a marker type implementing `metastruct::Field` for each selected field.
+    #[darling(default)]
+    lenses: Option<LensOpts>,
+    /// Generate an enum with a variant naming each selected field, called `<type_name>Field`.
+    #[darling(default)]
+    field_enum: Option<FieldEnumOpts>,
+    /// Methods getting fields by name or index as trait objects, named after the key.
+    #[darling(default)]
+    dyn_access: HashMap<Ident, DynAccessOpts>,
+    /// Methods iterating over the names of fields and the fields as trait objects, named after
+    /// the key.
+    #[darling(default)]
+    dyn_iter: HashMap<Ident, DynAccessOpts>,
+    /// Generate `set_field_from_str` and `field_to_string` methods for the selected fields.
+    #[darling(default)]
+    string_access: Option<StringAccessOpts>,
+    /// Generate `from_map` and `from_env` constructors, parsing each field from a string.
+    #[darling(default)]
+    from_kv: Option<FromKvOpts>,
+    /// Methods converting fields of the same type to and from maps keyed by field name.
+    #[darling(default)]
+    maps: HashMap<Ident, FieldMapOpts>,
+    /// Methods iterating over fields of the same type, and borrowing them as arrays.
+    #[darling(default)]
+    iters: HashMap<Ident, FieldIterOpts>,
+    /// Tuple type aliases of the selected fields, and conversions to and from them.
+    #[darling(default)]
+    tuples: HashMap<Ident, TupleOpts>,
+    /// Bounds that the types of all fields in each group must satisfy, like `"Hash + Clone"`.
+    #[darling(default)]
+    group_bounds: HashMap<Ident, TraitBounds>,
+}
+
+impl StructOpts {
+    /// All of the field selections configured by these options.
+    fn selections(&self) -> Vec<&SelectionOpts> {
+        let mut selections = vec![];
+        selections.extend(self.mappings.values().map(|opts| &opts.selection));
+        selections.extend(self.bimappings.values().map(|opts| &opts.selection));
+        selections.extend(self.dispatch.values().map(|opts| &opts.selection));
+        selections.extend(self.num_fields.values().map(|opts| &opts.selection));
+        selections.extend(self.views.values().map(|opts| &opts.selection));
+        selections.extend(self.lenses.iter().map(|opts| &opts.selection));
+        selections.extend(self.field_enum.iter().map(|opts| &opts.selection));
+        selections.extend(self.dyn_access.values().map(|opts| &opts.selection));
+        selections.extend(self.dyn_iter.values().map(|opts| &opts.selection));
+        selections.extend(self.string_access.iter().map(|opts| &opts.selection));
+        selections.extend(self.from_kv.iter().map(|opts| &opts.selection));
+        selections.extend(self.maps.values().map(|opts| &opts.selection));
+        selections.extend(self.iters.values().map(|opts| &opts.selection));
+        selections.extend(self.tuples.values().map(|opts| &opts.selection));
+        selections
+    }
 }
 
 #[proc_macro_attribute]
-pub fn metastruct(args: TokenStream, input: TokenStream) -> TokenStream {
-    let attr_args = match NestedMeta::parse_meta_list(args.into()) {
+pub fn metastruct(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    metastruct_impl(args.into(), input.into()).into()
+}
+
+/// Expand the `metastruct` attribute, separately from the compiler interface so it can be tested.
+fn metastruct_impl(args: TokenStream, input: TokenStream) -> TokenStream {
+    let attr_args = match NestedMeta::parse_meta_list(args) {
         Ok(args) => args,
-        Err(err) => return err.to_compile_error().into(),
+        Err(err) => return err.to_compile_error(),
+    };
+    let mut item = match syn::parse2::<ItemStruct>(input) {
+        Ok(item) => item,
+        Err(err) => return err.to_compile_error(),
     };
-    let mut item = parse_macro_input!(input as ItemStruct);
-
-    let type_name = &item.ident;
-
-    // Generics used for impl blocks.
-    let generics = &item.generics.split_for_impl();
 
     let opts = match StructOpts::from_list(&attr_args) {
         Ok(opts) => opts,
-        Err(err) => return err.write_errors().into(),
+        Err(err) => return err.write_errors(),
     };
 
-    let mut output_items: Vec<TokenStream> = vec![];
+    // The compiler expands `cfg_attr` on the struct before invoking this macro, so conditional
+    // configuration arrives here as further `metastruct` attributes. Handle them in this
+    // invocation, as the field attributes they rely on are removed from our output.
+    let mut struct_opts = vec![opts];
+    for attr in item.attrs.iter().filter(|attr| is_metastruct_attr(attr)) {
+        let opts = match parse_nested_metas(std::slice::from_ref(&attr.meta))
+            .and_then(|args| StructOpts::from_list(&args).map_err(|err| err.write_errors()))
+        {
+            Ok(opts) => opts,
+            Err(err) => return err,
+        };
+        struct_opts.push(opts);
+    }
 
-    // Collect field names and types.
-    let fields = item
+    // Check the fields named by selections against all fields, including conditional ones.
+    let field_names = item
         .fields
         .iter()
-        .map(|field| (field.ident.clone().expect(""), field.ty.clone()))
+        .filter_map(|field| field.ident.as_ref())
+        .collect::<Vec<_>>();
+    let errors = struct_opts
+        .iter()
+        .flat_map(StructOpts::selections)
+        .filter_map(|selection| exclude::check_included_fields(selection, &field_names).err())
         .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        return darling::Error::multiple(errors).write_errors();
+    }
 
-    // Collect field options.
-    let field_opts = item
-        .fields
+    // Collect the `cfg` predicates that determine which fields exist and which options apply.
+    let predicates = cfg::unique_predicates(item.fields.iter().flat_map(|field| {
+        cfg::cfg_predicates(&field.attrs).into_iter().chain(
+            cfg::metastruct_cfg_attrs(&field.attrs)
+                .into_iter()
+                .map(|(predicate, _)| predicate),
+        )
+    }));
+    if let Some(predicate) = predicates.get(cfg::MAX_PREDICATES) {
+        return darling::Error::custom(format!(
+            "at most {} distinct `cfg` predicates are supported on fields, as mapping macros are \
+             generated for each combination of them",
+            cfg::MAX_PREDICATES
+        ))
+        .with_span(predicate)
+        .write_errors();
+    }
+
+    let (fields, field_opts) = match gated_fields(&item) {
+        Ok(gated_fields) => gated_fields,
+        Err(err) => return err,
+    };
+    if let Err(err) = exclude::check_predicate_keys(&field_opts) {
+        return err.write_errors();
+    }
+
+    let field_enum_opts = struct_opts.iter().find_map(|opts| opts.field_enum.as_ref());
+    let mut output_items: Vec<TokenStream> = vec![];
+
+    for opts in &struct_opts {
+        output_items.extend(generate_items(
+            &item,
+            &fields,
+            &field_opts,
+            opts,
+            field_enum_opts,
+        ));
+    }
+
+    // Mapping macros are expanded in the crates invoking them, where the predicates can't be
+    // evaluated, so they're generated for each combination of the predicates instead.
+    for variant in cfg::cfg_variants(&predicates) {
+        let variant_fields = item
+            .fields
+            .iter()
+            .filter(|field| {
+                cfg::cfg_predicates(&field.attrs)
+                    .iter()
+                    .all(|predicate| variant.is_enabled(predicate))
+            })
+            .collect::<Vec<_>>();
+
+        // Collect field names and types.
+        let fields = variant_fields
+            .iter()
+            .map(|field| (field.ident.clone().expect(""), field.ty.clone()))
+            .collect::<Vec<_>>();
+
+        // Collect field options, including those enabled by `cfg_attr` in this variant.
+        let field_opts = variant_fields
+            .iter()
+            .map(|field| parse_field_opts(field, |predicate| variant.is_enabled(predicate)))
+            .collect::<Result<Vec<_>, TokenStream>>();
+        let field_opts = match field_opts {
+            Ok(field_opts) => field_opts,
+            Err(err) => return err,
+        };
+
+        let predicate = variant.predicate();
+        for opts in &struct_opts {
+            for items in generate_macros(&item, &fields, &field_opts, opts, field_enum_opts) {
+                output_items.push(cfg::cfg_gated(items, predicate.as_ref()));
+            }
+        }
+    }
+
+    // Output original struct definition after removing metastruct attributes from the fields.
+    for field in &mut item.fields {
+        field.attrs = discard_metastruct_attrs(&field.attrs);
+    }
+    item.attrs = discard_metastruct_attrs(&item.attrs);
+    output_items.push(quote! { #item });
+
+    TokenStream::from_iter(output_items)
+}
+
+/// Names and types of fields, and the options of each field.
+type FieldsWithOpts = (Vec<(Ident, Type)>, Vec<FieldOpts>);
+
+/// Collect the struct's fields and their options, with an entry for each combination of the
+/// predicates of a field's `cfg_attr` attributes.
+///
+/// Each entry is gated by its combination and by the field's own `cfg` predicates, so at most one
+/// entry of each field exists in any configuration.
+fn gated_fields(item: &ItemStruct) -> Result<FieldsWithOpts, TokenStream> {
+    let mut fields = vec![];
+    let mut field_opts = vec![];
+    for field in &item.fields {
+        let field_gate = cfg::CfgGate::all(cfg::cfg_predicates(&field.attrs));
+        let predicates = cfg::unique_predicates(
+            cfg::metastruct_cfg_attrs(&field.attrs)
+                .into_iter()
+                .map(|(predicate, _)| predicate),
+        );
+        for variant in cfg::cfg_variants(&predicates) {
+            // Skip combinations contradicting the field's own predicates.
+            let Some(gate) = field_gate.and(&variant) else {
+                continue;
+            };
+            let mut opts = parse_field_opts(field, |predicate| variant.is_enabled(predicate))?;
+            opts.cfg = gate;
+            fields.push((field.ident.clone().expect(""), field.ty.clone()));
+            field_opts.push(opts);
+        }
+    }
+    Ok((fields, field_opts))
+}
+
+/// Parse the options of `field`, including those applied by `cfg_attr` if `is_enabled` holds for
+/// its predicate.
+fn parse_field_opts(
+    field: &Field,
+    is_enabled: impl Fn(&Meta) -> bool,
+) -> Result<FieldOpts, TokenStream> {
+    let metas = field
+        .attrs
         .iter()
-        .map(|field| {
-            field
-                .attrs
-                .iter()
-                .filter(|attr| is_metastruct_attr(attr))
-                .find_map(|attr| {
-                    let meta = &attr.meta;
-                    Some(FieldOpts::from_meta(&meta).unwrap())
-                })
-                .unwrap_or_default()
-        })
+        .filter(|attr| is_metastruct_attr(attr))
+        .map(|attr| attr.meta.clone())
+        .chain(
+            cfg::metastruct_cfg_attrs(&field.attrs)
+                .into_iter()
+                .filter(|(predicate, _)| is_enabled(predicate))
+                .map(|(_, meta)| meta),
+        )
         .collect::<Vec<_>>();
+    let mut opts = parse_nested_metas(&metas)
+        .and_then(|items| FieldOpts::from_list(&items).map_err(|e| e.write_errors()))?;
+    opts.vis = field.vis.clone();
+    opts.attrs = discard_metastruct_attrs(&field.attrs);
+    Ok(opts)
+}
+
+/// Generate the mapping, bi-mapping and dispatch macros configured by `opts`, for the fields of
+/// one combination of `cfg` predicates.
+///
+/// The field enum may be configured by any of the struct's `metastruct` attributes, and is
+/// passed separately as `field_enum_opts`.
+fn generate_macros(
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    opts: &StructOpts,
+    field_enum_opts: Option<&FieldEnumOpts>,
+) -> Vec<TokenStream> {
+    let type_name = &item.ident;
+
+    let mut output_items: Vec<TokenStream> = vec![];
 
     // Generate mapping macros.
     for (mapping_macro_name, mapping_opts) in &opts.mappings {
+        if let Err(err) = mapping::check_mapping_field_enum(
+            mapping_macro_name,
+            fields,
+            field_opts,
+            mapping_opts,
+            field_enum_opts,
+        ) {
+            output_items.push(err);
+            continue;
+        }
         output_items.push(mapping::generate_mapping_macro(
             mapping_macro_name,
             type_name,
-            &fields,
-            &field_opts,
+            fields,
+            field_opts,
             mapping_opts,
         ));
     }
@@ -137,38 +614,267 @@ pub fn metastruct(args: TokenStream, input: TokenStream) -> TokenStream {
         output_items.push(mapping::generate_bimapping_macro(
             mapping_macro_name,
             type_name,
-            &fields,
-            &field_opts,
+            fields,
+            field_opts,
+            mapping_opts,
+        ));
+    }
+
+    // Generate dispatch macros.
+    for (dispatch_macro_name, dispatch_opts) in &opts.dispatch {
+        output_items.push(mapping::generate_dispatch_macro(
+            dispatch_macro_name,
+            type_name,
+            fields,
+            field_opts,
+            dispatch_opts,
+        ));
+    }
+
+    output_items
+}
+
+/// Generate all the items configured by `opts` other than macros, once for all configurations.
+///
+/// The field enum may be configured by any of the struct's `metastruct` attributes, and is
+/// passed separately as `field_enum_opts`.
+fn generate_items(
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    opts: &StructOpts,
+    field_enum_opts: Option<&FieldEnumOpts>,
+) -> Vec<TokenStream> {
+    let type_name = &item.ident;
+
+    // Generics used for impl blocks.
+    let generics = &item.generics.split_for_impl();
+
+    let mut output_items: Vec<TokenStream> = vec![];
+
+    // Generate methods applying closures to the fields of mappings. The errors of mappings which
+    // fail the check of their field enum are reported by `generate_macros`.
+    for (mapping_macro_name, mapping_opts) in &opts.mappings {
+        let checked = mapping::check_mapping_field_enum(
+            mapping_macro_name,
+            fields,
+            field_opts,
             mapping_opts,
+            field_enum_opts,
+        );
+        if let (Some(method_name), Ok(())) = (&mapping_opts.method, checked) {
+            output_items.push(mapping::generate_mapping_method(
+                mapping_macro_name,
+                method_name,
+                item,
+                fields,
+                field_opts,
+                mapping_opts,
+            ));
+        }
+    }
+
+    // Generate assertions of the bounds required by mappings.
+    for (selection, trait_bounds) in opts
+        .mappings
+        .values()
+        .filter_map(|mapping_opts| {
+            Some((
+                &mapping_opts.selection,
+                mapping_opts.assert_bounds.as_ref()?,
+            ))
+        })
+        .chain(opts.bimappings.values().filter_map(|mapping_opts| {
+            Some((
+                &mapping_opts.selection,
+                mapping_opts.assert_bounds.as_ref()?,
+            ))
+        }))
+    {
+        output_items.push(bounds::generate_bound_assertions(
+            item,
+            fields,
+            field_opts,
+            selection,
+            trait_bounds,
+        ));
+    }
+
+    // Generate assertions of the bounds required by groups.
+    for (group, trait_bounds) in &opts.group_bounds {
+        let selection = SelectionOpts {
+            groups: Some(GroupExpr::Group(group.clone())),
+            ..SelectionOpts::default()
+        };
+        output_items.push(bounds::generate_bound_assertions(
+            item,
+            fields,
+            field_opts,
+            &selection,
+            trait_bounds,
         ));
     }
 
     // Generate `NumFields` implementations.
-    for (_, num_fields_opts) in &opts.num_fields {
+    for (num_fields_name, num_fields_opts) in &opts.num_fields {
         output_items.push(num_fields::generate_num_fields_impl(
+            num_fields_name,
             type_name,
             generics,
-            &fields,
-            &field_opts,
+            fields,
+            field_opts,
             num_fields_opts,
         ));
     }
 
-    // Output original struct definition after removing metastruct attributes from the fields.
-    for field in &mut item.fields {
-        field.attrs = discard_metastruct_attrs(&field.attrs);
+    // Generate view structs.
+    for (view_name, view_opts) in &opts.views {
+        output_items.push(view::generate_views(
+            view_name, item, fields, field_opts, view_opts,
+        ));
     }
-    output_items.push(quote! { #item }.into());
 
-    TokenStream::from_iter(output_items)
+    // Generate methods splitting the struct into several views.
+    for (split_name, split_opts) in &opts.splits {
+        output_items.push(view::generate_split(
+            split_name,
+            item,
+            fields,
+            field_opts,
+            &opts.views,
+            split_opts,
+        ));
+    }
+
+    // Generate methods moving the struct into and out of several owned views.
+    for (partition_name, partition_opts) in &opts.partitions {
+        output_items.push(view::generate_partition(
+            partition_name,
+            item,
+            fields,
+            field_opts,
+            &opts.views,
+            partition_opts,
+        ));
+    }
+
+    // Generate field markers.
+    if let Some(lens_opts) = &opts.lenses {
+        output_items.push(lens::generate_lenses(item, fields, field_opts, lens_opts));
+    }
+
+    // Generate the field enum.
+    if let Some(field_enum_opts) = &opts.field_enum {
+        output_items.push(field_enum::generate_field_enum(
+            item,
+            fields,
+            field_opts,
+            field_enum_opts,
+        ));
+    }
+
+    // Generate methods for dynamic access to fields.
+    for (key, dyn_access_opts) in &opts.dyn_access {
+        output_items.push(dyn_access::generate_dyn_access(
+            key,
+            item,
+            fields,
+            field_opts,
+            dyn_access_opts,
+        ));
+    }
+
+    // Generate iterators over fields as trait objects.
+    for (key, dyn_iter_opts) in &opts.dyn_iter {
+        output_items.push(dyn_access::generate_dyn_iter(
+            key,
+            item,
+            fields,
+            field_opts,
+            dyn_iter_opts,
+        ));
+    }
+
+    // Generate methods for access to fields as strings.
+    if let Some(string_access_opts) = &opts.string_access {
+        output_items.push(string_access::generate_string_access(
+            item,
+            fields,
+            field_opts,
+            string_access_opts,
+        ));
+    }
+
+    // Generate constructors from maps of keys to strings.
+    if let Some(from_kv_opts) = &opts.from_kv {
+        output_items.push(from_kv::generate_from_kv(
+            item,
+            fields,
+            field_opts,
+            from_kv_opts,
+        ));
+    }
+
+    // Generate conversions to and from maps.
+    for (key, field_map_opts) in &opts.maps {
+        output_items.push(field_map::generate_field_map(
+            key,
+            item,
+            fields,
+            field_opts,
+            field_map_opts,
+        ));
+    }
+
+    // Generate iterators over fields.
+    for (key, field_iter_opts) in &opts.iters {
+        output_items.push(field_iter::generate_field_iter(
+            key,
+            item,
+            fields,
+            field_opts,
+            field_iter_opts,
+        ));
+    }
+
+    // Generate tuple conversions.
+    for (key, tuple_opts) in &opts.tuples {
+        output_items.push(tuple::generate_tuple(
+            key, item, fields, field_opts, tuple_opts,
+        ));
+    }
+
+    output_items
+}
+
+/// Combine the nested items of several `metastruct(..)` metas into a single list.
+fn parse_nested_metas(metas: &[Meta]) -> Result<Vec<NestedMeta>, TokenStream> {
+    let mut items = vec![];
+    for meta in metas {
+        match meta {
+            Meta::Path(_) => {}
+            Meta::List(list) => items.extend(
+                NestedMeta::parse_meta_list(list.tokens.clone())
+                    .map_err(|err| err.to_compile_error())?,
+            ),
+            Meta::NameValue(_) => {
+                return Err(darling::Error::unsupported_format("name-value")
+                    .with_span(meta)
+                    .write_errors())
+            }
+        }
+    }
+    Ok(items)
 }
 
 /// Keep all non-metastruct-related attributes from an array.
+///
+/// Conditional `metastruct` attributes are also removed from any `cfg_attr` attributes.
 fn discard_metastruct_attrs(attrs: &[Attribute]) -> Vec<Attribute> {
     attrs
         .iter()
         .filter(|attr| !is_metastruct_attr(attr))
-        .cloned()
+        .filter_map(cfg::discard_conditional_metastruct_attrs)
         .collect()
 }
 
@@ -181,5 +887,144 @@ fn is_metastruct_attr(attr: &Attribute) -> bool {
 fn is_attr_with_ident(attr: &Attribute, ident: &str) -> bool {
     attr.path()
         .get_ident()
-        .map_or(false, |attr_ident| attr_ident.to_string() == ident)
+        .is_some_and(|attr_ident| attr_ident == ident)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Expand `metastruct` with `args` on a struct with fields of the given types.
+    fn expand(args: TokenStream, field_types: &[TokenStream]) -> String {
+        let fields = field_types.iter().enumerate().map(|(i, field_type)| {
+            let field = quote::format_ident!("f{i}");
+            quote! { #field: #field_type }
+        });
+        metastruct_impl(args, quote! { struct Big { #(#fields,)* } }).to_string()
+    }
+
+    /// Types of `num_fields` fields, the first half of which are `u64` and the rest `String`.
+    fn grouped(num_fields: usize) -> Vec<TokenStream> {
+        (0..num_fields)
+            .map(|i| {
+                if i < num_fields / 2 {
+                    quote! { u64 }
+                } else {
+                    quote! { String }
+                }
+            })
+            .collect()
+    }
+
+    /// Types of `num_fields` fields, alternating between `u64` and `String`.
+    fn interleaved(num_fields: usize) -> Vec<TokenStream> {
+        (0..num_fields)
+            .map(|i| {
+                if i % 2 == 0 {
+                    quote! { u64 }
+                } else {
+                    quote! { String }
+                }
+            })
+            .collect()
+    }
+
+    /// Types of `num_fields` fields, which are all distinct but equally long.
+    fn distinct(num_fields: usize) -> Vec<TokenStream> {
+        (10..10 + num_fields)
+            .map(|len| quote! { [u8; #len] })
+            .collect()
+    }
+
+    #[test]
+    fn mapping_expansion_reduced_by_shared_coercions() {
+        // Fields of distinct types each need their own coercion, as every field did before
+        // coercions were shared, and the expansion is otherwise the same as for fields of one type.
+        // Sharing coercions between the fields of one type removes more than a third of it.
+        let args = quote! { mappings(map_big(field_names)) };
+        let shared = expand(args.clone(), &vec![quote! { [u8; 10usize] }; 32]).len();
+        let unshared = expand(args, &distinct(32)).len();
+        assert!(
+            3 * shared < 2 * unshared,
+            "{shared} bytes with shared coercions, {unshared} without"
+        );
+    }
+
+    #[test]
+    fn mapping_coercions_shared_between_fields_of_the_same_type() {
+        let args = quote! { mappings(map_big(), map_big_named(field_names, mutable)) };
+        // One coercion per run of fields of the same type, in each of the named and unnamed arms
+        // of each mapping.
+        let expansion = expand(args.clone(), &grouped(32));
+        assert_eq!(expansion.matches("FnMut").count(), 2 * 2 * 2);
+        // Coercions can't be shared between fields separated by a field of another type.
+        let expansion = expand(args, &interleaved(32));
+        assert_eq!(expansion.matches("FnMut").count(), 2 * 2 * 32);
+    }
+
+    #[test]
+    fn mapping_expansion_size() {
+        // The expansion should grow linearly and slowly with the number of fields, as it's
+        // repeated at every call site of the mapping.
+        let args = quote! { mappings(map_big(field_names)) };
+        let small = expand(args.clone(), &grouped(8)).len();
+        let large = expand(args.clone(), &grouped(32)).len();
+        assert!(
+            large < 4 * small,
+            "{small} bytes for 8 fields, {large} for 32"
+        );
+        assert!(large < 8 * 1024, "{large} bytes for 32 fields");
+
+        // Fields of interleaved types each need a coercion, which bounds the expansion for any
+        // order of fields.
+        let small = expand(args.clone(), &interleaved(8)).len();
+        let large = expand(args, &interleaved(32)).len();
+        assert!(
+            large < 4 * small,
+            "{small} bytes for 8 interleaved fields, {large} for 32"
+        );
+        assert!(large < 12 * 1024, "{large} bytes for 32 interleaved fields");
+    }
+
+    #[test]
+    fn only_macros_generated_for_each_cfg_combination() {
+        let expansion = metastruct_impl(
+            quote! { mappings(map_foo()), num_fields(all()), field_enum, views(values()) },
+            quote! {
+                struct Foo {
+                    a: u64,
+                    #[cfg(feature = "b")]
+                    b: u64,
+                    #[cfg_attr(feature = "c", metastruct(exclude))]
+                    c: u64,
+                }
+            },
+        )
+        .to_string();
+        // The mapping macro is generated for each combination of the two predicates.
+        assert_eq!(expansion.matches("macro_rules !").count(), 4);
+        // Other items are generated once, with the `cfg` of each field.
+        assert_eq!(expansion.matches("NumFields <").count(), 1);
+        assert_eq!(expansion.matches("enum FooField").count(), 1);
+        assert_eq!(expansion.matches("struct FooValuesRef").count(), 1);
+        // The number of fields is only known once the predicates are evaluated.
+        assert!(expansion
+            .contains("# [cfg (feature = \"b\")] let __metastruct_n = __metastruct_n + 1 ;"));
+    }
+
+    #[test]
+    fn too_many_cfg_predicates() {
+        let fields = (0..=cfg::MAX_PREDICATES).map(|i| {
+            let field = quote::format_ident!("f{i}");
+            let feature = format!("f{i}");
+            quote! { #[cfg(feature = #feature)] #field: u64 }
+        });
+        let expansion = metastruct_impl(
+            quote! { num_fields(all()) },
+            quote! { struct Big { #(#fields,)* } },
+        )
+        .to_string();
+        assert!(expansion.contains("compile_error"));
+        assert!(expansion.contains("at most"));
+    }
 }
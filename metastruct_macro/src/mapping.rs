@@ -1,8 +1,151 @@
-use crate::{exclude::calculate_excluded_fields, BiMappingOpts, FieldOpts, MappingOpts};
+use crate::{
+    cfg::cfg_errors,
+    exclude::{
+        calculate_field_predicates, calculate_selected_field_opts, calculate_selected_fields,
+    },
+    field_enum::{field_enum_name, field_variant_name},
+    homogeneous::common_field_type,
+    BiMappingOpts, DispatchOpts, FieldEnumOpts, FieldOpts, MappingOpts,
+};
+use darling::Error;
 use itertools::Itertools;
-use proc_macro::TokenStream;
-use quote::quote;
-use syn::{Ident, Type};
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{spanned::Spanned, Ident, ItemStruct, Path, Type};
+
+/// Macro parameters, arguments and bindings for the context of mappings with conditional fields.
+struct ContextTokens {
+    /// Context parameter of the public macro arms.
+    param: TokenStream,
+    /// Context argument passed between public macro arms.
+    arg: TokenStream,
+    /// Binding of a reference to the context, which is `()` for mappings without conditional
+    /// fields.
+    ///
+    /// The reference is passed to the nested macro arms, including those of the mappings of
+    /// flattened fields, so that their own conditional fields are evaluated with the same context.
+    binding: TokenStream,
+}
+
+impl ContextTokens {
+    fn new(predicates: &[Vec<&Path>]) -> Self {
+        if predicates.iter().all(Vec::is_empty) {
+            return Self {
+                param: quote! {},
+                arg: quote! {},
+                binding: quote! { let __metastruct_ctx = &(); },
+            };
+        }
+        Self {
+            param: quote! { $ctx:expr, },
+            arg: quote! { $ctx, },
+            binding: quote! { let __metastruct_ctx = &$ctx; },
+        }
+    }
+}
+
+/// Coerce the mapping closure to a trait object taking the given parameters for one field.
+///
+/// The statement is spanned to the field's type, so that if the closure can't be applied to the
+/// field, the error points at the field's declaration rather than at the mapping's definition.
+/// Errors within the closure's body are reported at the body, which is the caller's code, and
+/// can only identify the field by its type.
+fn closure_coercion(field_type: &Type, params: TokenStream) -> TokenStream {
+    let closure = quote! { $f };
+    quote_spanned! {field_type.span()=>
+        let __metastruct_f: &mut dyn FnMut(#params) -> _ = &mut #closure;
+    }
+}
+
+/// Generic function applying the mapping closure to one field, for statically dispatched mappings.
+///
+/// The closure's parameter types are deduced from the field passed before it, and the function is
+/// monomorphised for each field type, so that the closure can be inlined.
+fn static_dispatch_helper(named: bool, index_type: &TokenStream) -> TokenStream {
+    let (name_param, name_type, name_arg) = if named {
+        (
+            quote! { name: &'static str, },
+            quote! { &'static str, },
+            quote! { name, },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {})
+    };
+    quote! {
+        #[inline(always)]
+        fn __metastruct_apply<T, R>(
+            index: #index_type,
+            #name_param
+            field: T,
+            f: &mut impl FnMut(#index_type, #name_type T) -> R,
+        ) -> R {
+            f(index, #name_arg field)
+        }
+    }
+}
+
+/// Guard the statements visiting a field with the field's `only_if` predicates.
+fn visit_if(predicates: &[&Path], ctx: TokenStream, stmts: TokenStream) -> TokenStream {
+    if predicates.is_empty() {
+        stmts
+    } else {
+        quote! {
+            if #(#predicates(#ctx))&&* {
+                #stmts
+            }
+        }
+    }
+}
+
+/// Check that the struct's field enum has a variant for each field of a mapping passing it.
+///
+/// Returns the error to report in place of the mapping and its method, if any.
+pub(crate) fn check_mapping_field_enum(
+    macro_name: &Ident,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    mapping_opts: &MappingOpts,
+    field_enum_opts: Option<&FieldEnumOpts>,
+) -> Result<(), TokenStream> {
+    if !mapping_opts.field_enum {
+        return Ok(());
+    }
+    let Some(field_enum_opts) = field_enum_opts else {
+        return Err(Error::custom(format!(
+            "mapping `{macro_name}` passes the field enum, which requires the `field_enum` option"
+        ))
+        .with_span(macro_name)
+        .write_errors());
+    };
+    let variants = calculate_selected_fields(&field_enum_opts.selection, fields, field_opts);
+    let selected_fields = calculate_selected_fields(&mapping_opts.selection, fields, field_opts);
+    let selected_field_opts =
+        calculate_selected_field_opts(&mapping_opts.selection, fields, field_opts);
+    let errors = selected_fields
+        .iter()
+        .zip(&selected_field_opts)
+        .filter_map(|((field, _), field_opts)| {
+            if field_opts.flatten.contains_key(macro_name) {
+                Some(Error::custom(format!(
+                    "mapping `{macro_name}` passes the field enum, and cannot flatten `{field}`"
+                )))
+            } else if !variants.iter().any(|(variant, _)| variant == field) {
+                Some(Error::custom(format!(
+                    "mapping `{macro_name}` passes the field enum, which has no variant for \
+                     `{field}`"
+                )))
+            } else {
+                None
+            }
+            .map(|err| err.with_span(field))
+        })
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::multiple(errors).write_errors())
+    }
+}
 
 pub(crate) fn generate_mapping_macro(
     macro_name: &Ident,
@@ -11,17 +154,13 @@ pub(crate) fn generate_mapping_macro(
     field_opts: &[FieldOpts],
     mapping_opts: &MappingOpts,
 ) -> TokenStream {
-    let exclude_idents = calculate_excluded_fields(
-        &mapping_opts.exclude,
-        &mapping_opts.groups,
-        fields,
-        field_opts,
-    );
-    let (selected_fields, selected_field_types): (Vec<_>, Vec<_>) = fields
-        .iter()
-        .filter(|(field_name, _)| !exclude_idents.contains(&field_name))
-        .cloned()
-        .unzip();
+    let (selected_fields, selected_field_types): (Vec<_>, Vec<_>) =
+        calculate_selected_fields(&mapping_opts.selection, fields, field_opts)
+            .into_iter()
+            .cloned()
+            .unzip();
+    let selected_field_opts =
+        calculate_selected_field_opts(&mapping_opts.selection, fields, field_opts);
 
     let field_reference = if mapping_opts.mutable {
         quote! { ref mut }
@@ -40,21 +179,112 @@ pub(crate) fn generate_mapping_macro(
         })
         .collect::<Vec<_>>();
 
-    let function_call_exprs = selected_fields
-        .iter()
-        .map(|field| {
-            if mapping_opts.fallible {
-                quote! { __metastruct_f(__metastruct_i, #field)? }
-            } else {
-                quote! { __metastruct_f(__metastruct_i, #field) }
-            }
-        })
-        .collect::<Vec<_>>();
+    let predicates =
+        calculate_field_predicates(macro_name, &mapping_opts.selection, fields, field_opts);
+    let ContextTokens {
+        param: ctx_param,
+        arg: ctx_arg,
+        binding: ctx_binding,
+    } = ContextTokens::new(&predicates);
 
-    quote! {
-        #[macro_export]
-        macro_rules! #macro_name {
-            (&$lifetime:tt _, $v:expr, $f:expr) => {
+    let skip = quote! { @metastruct_nested_skip };
+    let field_enum = field_enum_name(type_name);
+    let index_type = if mapping_opts.field_enum {
+        quote! { #field_enum }
+    } else {
+        quote! { usize }
+    };
+
+    // Statements visiting each field, with or without passing the field's name to the closure.
+    //
+    // Consecutive fields of the same type share a single coercion of the closure, which keeps
+    // the expansion at each call site small for structs with many fields of a few types. Fields
+    // of the same type can't share a coercion across fields of other types: each coercion holds
+    // a mutable borrow of a fresh closure, which may itself mutably borrow its captures, so only
+    // one coercion can be live at a time. Structs whose field types alternate therefore still
+    // need a coercion per field, as before coercions were shared, unless the mapping uses
+    // `static_dispatch`, which needs no coercions.
+    let field_visits = |named: bool| {
+        let name_type = named.then(|| quote! { &'static str, });
+        let fields = selected_fields
+            .iter()
+            .zip(&selected_field_types)
+            .zip(&mapping_function_input_types)
+            .zip(&selected_field_opts)
+            .zip(&predicates);
+        let mut visits = vec![];
+        for (_, run) in &fields.chunk_by(|((((_, _), input_type), field_opts), _)| {
+            // Flattened fields are visited by the inner mapping, and never share a coercion.
+            let flattened = field_opts.flatten.contains_key(macro_name);
+            (!flattened).then(|| input_type.to_string())
+        }) {
+            let mut coerced = false;
+            for ((((field, field_type), input_type), field_opts), predicates) in run {
+                if let Some(inner_macro) = field_opts.flatten.get(macro_name) {
+                    let prefix = format!("{field}.");
+                    let nested = nested_arm_name(named);
+                    let visit = quote! {
+                        #inner_macro!(#nested [$($prefix,)* #prefix] $i, $ctx, &$lifetime _, #field, $f);
+                    };
+                    // If the field is skipped, its fields still count towards the indices of
+                    // later fields.
+                    visits.push(if predicates.is_empty() {
+                        visit
+                    } else {
+                        quote! {
+                            if #(#predicates($ctx))&&* {
+                                #visit
+                            } else {
+                                #inner_macro!(#skip $i);
+                            }
+                        }
+                    });
+                    continue;
+                }
+
+                if !coerced && !mapping_opts.static_dispatch {
+                    visits.push(closure_coercion(
+                        field_type,
+                        quote! { #index_type, #name_type &$lifetime #input_type },
+                    ));
+                    coerced = true;
+                }
+
+                let field_name = field.to_string();
+                let name = named.then(|| quote! { concat!($($prefix,)* #field_name), });
+                let index = if mapping_opts.field_enum {
+                    let variant = field_variant_name(field);
+                    quote! { #field_enum::#variant }
+                } else {
+                    quote! { $i }
+                };
+                let call = if mapping_opts.static_dispatch {
+                    quote! { __metastruct_apply(#index, #name #field, &mut $f) }
+                } else {
+                    quote! { __metastruct_f(#index, #name #field) }
+                };
+                let call = if mapping_opts.fallible {
+                    quote! { #call? }
+                } else {
+                    call
+                };
+                let visit = visit_if(predicates, quote! { $ctx }, quote! { #call; });
+                visits.push(quote! {
+                    #visit
+                    $i += 1;
+                });
+            }
+        }
+        visits
+    };
+    let nested_arm = |named: bool| {
+        let arm_name = nested_arm_name(named);
+        let field_visits = field_visits(named);
+        let helper = mapping_opts
+            .static_dispatch
+            .then(|| static_dispatch_helper(named, &index_type));
+        quote! {
+            (#arm_name [$($prefix:literal),*] $i:ident, $ctx:ident, &$lifetime:tt _, $v:expr, $f:expr) => {
                 match $v {
                     #type_name {
                         #(
@@ -62,21 +292,275 @@ pub(crate) fn generate_mapping_macro(
                         )*
                         ..
                     } => {
-                        let mut __metastruct_i: usize = 0;
-                        #(
-                            let __metastruct_f: &mut dyn FnMut(usize, &$lifetime #mapping_function_input_types) -> _ = &mut $f;
-                            #function_call_exprs;
-                            __metastruct_i += 1;
-                        )*
+                        #helper
+                        #(#field_visits)*
                     }
                 }
             };
-            ($v:expr, $f:expr) => {
-                #macro_name!(&'_ _, $v, $f)
+        }
+    };
+    let unnamed_arm = nested_arm(false);
+    let named_arm = nested_arm(true);
+
+    // Advance the index past the selected fields without visiting them, for skipped flattened
+    // fields of outer mappings.
+    let skips =
+        selected_field_opts
+            .iter()
+            .map(|field_opts| match field_opts.flatten.get(macro_name) {
+                Some(inner_macro) => quote! { #inner_macro!(#skip $i); },
+                None => quote! { $i += 1; },
+            });
+    let skip_arm = quote! {
+        (#skip $i:ident) => {
+            #(#skips)*
+        };
+    };
+    let top_level_arm_name = nested_arm_name(mapping_opts.field_names);
+
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            #unnamed_arm
+            #named_arm
+            #skip_arm
+            (&$lifetime:tt _, $v:expr, #ctx_param $f:expr) => {{
+                #ctx_binding
+                let mut __metastruct_i: usize = 0;
+                #macro_name!(#top_level_arm_name [] __metastruct_i, __metastruct_ctx, &$lifetime _, $v, $f)
+            }};
+            ($v:expr, #ctx_param $f:expr) => {
+                #macro_name!(&'_ _, $v, #ctx_arg $f)
             };
         }
     }
-    .into()
+}
+
+/// Generate an inherent method applying a closure to the fields selected by a mapping.
+///
+/// The method invokes the mapping macro, which must be in scope where the struct is defined.
+pub(crate) fn generate_mapping_method(
+    macro_name: &Ident,
+    method_name: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    mapping_opts: &MappingOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    let selected_fields = calculate_selected_fields(&mapping_opts.selection, fields, field_opts);
+    let selected_field_opts =
+        calculate_selected_field_opts(&mapping_opts.selection, fields, field_opts);
+
+    // Conditional fields, and flattened fields without `method_trait`, prevent the method from
+    // being generated in the configurations where they exist.
+    let predicates =
+        calculate_field_predicates(macro_name, &mapping_opts.selection, fields, field_opts);
+    let conditional = selected_field_opts
+        .iter()
+        .zip(&predicates)
+        .filter(|(_, predicates)| !predicates.is_empty())
+        .map(|(field_opts, _)| &field_opts.cfg)
+        .collect();
+    let flattened = selected_field_opts
+        .iter()
+        .filter(|field_opts| {
+            mapping_opts.method_trait.is_none() && field_opts.flatten.contains_key(macro_name)
+        })
+        .map(|field_opts| &field_opts.cfg)
+        .collect();
+    let errors = vec![
+        (
+            conditional,
+            Error::custom(format!(
+                "method `{method_name}` cannot be generated for mapping `{macro_name}`, \
+                 which has conditional fields"
+            ))
+            .with_span(method_name),
+        ),
+        (
+            flattened,
+            Error::custom(format!(
+                "method `{method_name}` requires `method_trait`, as mapping `{macro_name}` \
+                 has flattened fields"
+            ))
+            .with_span(method_name),
+        ),
+    ];
+
+    let (value_type, assertions) = match &mapping_opts.method_trait {
+        Some(trait_bounds) => {
+            let bounds = &trait_bounds.bounds;
+            (quote! { dyn #bounds }, quote! {})
+        }
+        None => {
+            let (value_type, assertions) =
+                match common_field_type(method_name, &selected_fields, &selected_field_opts) {
+                    Ok(common) => common,
+                    Err(err) => return err,
+                };
+            (quote! { #value_type }, assertions)
+        }
+    };
+
+    let (receiver, value_ref) = if mapping_opts.mutable {
+        (quote! { &mut self }, quote! { &mut (#value_type) })
+    } else {
+        (quote! { &self }, quote! { &(#value_type) })
+    };
+    let index_type = if mapping_opts.field_enum {
+        let field_enum = field_enum_name(type_name);
+        quote! { #field_enum }
+    } else {
+        quote! { usize }
+    };
+    let (name_type, name_param, name_arg) = if mapping_opts.field_names {
+        (
+            quote! { &'static str, },
+            quote! { __metastruct_name, },
+            quote! { __metastruct_name, },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {})
+    };
+
+    let doc = format!("Apply `f` to each of the fields mapped by `{macro_name}!`.");
+    let (generics, return_type, closure_return, body_end) = if mapping_opts.fallible {
+        (
+            quote! { <__MetastructE> },
+            quote! { -> ::core::result::Result<(), __MetastructE> },
+            quote! { -> ::core::result::Result<(), __MetastructE> },
+            quote! { ::core::result::Result::Ok(()) },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {}, quote! {})
+    };
+
+    let items = quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #doc]
+            #vis fn #method_name #generics(
+                #receiver,
+                mut f: impl FnMut(#index_type, #name_type #value_ref) #closure_return,
+            ) #return_type {
+                #assertions
+                #macro_name!(self, |__metastruct_index, #name_param __metastruct_value| {
+                    f(__metastruct_index, #name_arg __metastruct_value)
+                });
+                #body_end
+            }
+        }
+    };
+    cfg_errors(errors, items)
+}
+
+pub(crate) fn generate_dispatch_macro(
+    macro_name: &Ident,
+    type_name: &Ident,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    dispatch_opts: &DispatchOpts,
+) -> TokenStream {
+    let (selected_fields, selected_field_types): (Vec<_>, Vec<_>) =
+        calculate_selected_fields(&dispatch_opts.selection, fields, field_opts)
+            .into_iter()
+            .cloned()
+            .unzip();
+    let selected_field_opts =
+        calculate_selected_field_opts(&dispatch_opts.selection, fields, field_opts);
+    if let Some((field, _)) = selected_fields
+        .iter()
+        .zip(&selected_field_opts)
+        .find(|(_, field_opts)| field_opts.flatten.contains_key(macro_name))
+    {
+        return Error::custom(format!(
+            "dispatch `{macro_name}` applies the closure to a single field, and cannot flatten \
+             `{field}`"
+        ))
+        .with_span(field)
+        .write_errors();
+    }
+    let indices = 0..selected_fields.len();
+
+    // Conditional fields are only dispatched to if their predicates hold for the context.
+    let predicates =
+        calculate_field_predicates(macro_name, &dispatch_opts.selection, fields, field_opts);
+    let conditional = predicates.iter().any(|predicates| !predicates.is_empty());
+    let ContextTokens {
+        param: ctx_param,
+        arg: ctx_arg,
+        binding: ctx_binding,
+    } = ContextTokens::new(&predicates);
+    let ctx_binding = conditional.then_some(ctx_binding);
+
+    let (field_reference, field_reference_type) = if dispatch_opts.mutable {
+        (quote! { ref mut }, quote! { mut })
+    } else {
+        (quote! { ref }, quote! {})
+    };
+    let visits = selected_fields
+        .iter()
+        .zip(&selected_field_types)
+        .zip(&predicates)
+        .map(|((field, field_type), predicates)| {
+            let coercion = closure_coercion(
+                field_type,
+                quote! { &$lifetime #field_reference_type #field_type },
+            );
+            let call = if dispatch_opts.fallible {
+                quote! { __metastruct_f(#field)? }
+            } else {
+                quote! { __metastruct_f(#field) }
+            };
+            let visit = quote! {{
+                #coercion
+                ::core::option::Option::Some(#call)
+            }};
+            if predicates.is_empty() {
+                visit
+            } else {
+                quote! {
+                    if #(#predicates(__metastruct_ctx))&&* {
+                        #visit
+                    } else {
+                        ::core::option::Option::None
+                    }
+                }
+            }
+        });
+
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            (&$lifetime:tt _, $v:expr, $index:expr, #ctx_param $f:expr) => {{
+                #ctx_binding
+                match ($index, $v) {
+                    #(
+                        (#indices, #type_name { #field_reference #selected_fields, .. }) => #visits
+                    )*
+                    _ => ::core::option::Option::None,
+                }
+            }};
+            ($v:expr, $index:expr, #ctx_param $f:expr) => {
+                #macro_name!(&'_ _, $v, $index, #ctx_arg $f)
+            };
+        }
+    }
+}
+
+/// Name of the hidden macro arm used to visit the fields of a (possibly flattened) struct.
+///
+/// The arm takes the prefix to apply to field names, and the name of the variable used to count
+/// fields, so that flattened fields are visited with their full path and global index.
+fn nested_arm_name(named: bool) -> TokenStream {
+    if named {
+        quote! { @metastruct_nested_named }
+    } else {
+        quote! { @metastruct_nested }
+    }
 }
 
 pub(crate) fn generate_bimapping_macro(
@@ -87,19 +571,12 @@ pub(crate) fn generate_bimapping_macro(
     mapping_opts: &BiMappingOpts,
 ) -> TokenStream {
     let right_type_name = &mapping_opts.other_type;
-    let exclude_idents = calculate_excluded_fields(
-        &mapping_opts.exclude,
-        &mapping_opts.groups,
-        left_fields,
-        left_field_opts,
-    );
     let (left_selected_fields, right_selected_fields, left_selected_field_types): (
         Vec<_>,
         Vec<_>,
         Vec<_>,
-    ) = left_fields
-        .iter()
-        .filter(|(field_name, _)| !exclude_idents.contains(&field_name))
+    ) = calculate_selected_fields(&mapping_opts.selection, left_fields, left_field_opts)
+        .into_iter()
         .map(|(field_name, left_type)| {
             let right_field_name = Ident::new(&format!("{field_name}_r"), field_name.span());
             (field_name, right_field_name, left_type)
@@ -129,29 +606,55 @@ pub(crate) fn generate_bimapping_macro(
         (quote! { ref }, quote! { &'_ })
     };
 
-    let mapping_function_types = left_selected_field_types
+    // Each field needs its own coercion, as the types of the other struct's fields are unknown.
+    let coercions = left_selected_field_types
         .iter()
         .map(|field_type| {
-            quote! { &mut dyn FnMut(usize, #left_field_ref_typ #field_type, #right_field_ref_typ _) -> _ }
+            closure_coercion(
+                field_type,
+                quote! { usize, #left_field_ref_typ #field_type, #right_field_ref_typ _ },
+            )
         })
         .collect::<Vec<_>>();
 
-    let function_call_exprs = left_selected_fields
+    let predicates = calculate_field_predicates(
+        macro_name,
+        &mapping_opts.selection,
+        left_fields,
+        left_field_opts,
+    );
+    let ContextTokens {
+        param: ctx_param,
+        binding: ctx_binding,
+        ..
+    } = ContextTokens::new(&predicates);
+
+    let field_visits = left_selected_fields
         .iter()
         .zip(&right_selected_fields)
-        .map(|(left_field, right_field)| {
-            if mapping_opts.fallible {
+        .zip(&coercions)
+        .zip(&predicates)
+        .map(|(((left_field, right_field), coercion), predicates)| {
+            let call = if mapping_opts.fallible {
                 quote! { __metastruct_f(__metastruct_i, #left_field, #right_field)? }
             } else {
                 quote! { __metastruct_f(__metastruct_i, #left_field, #right_field) }
-            }
+            };
+            visit_if(
+                predicates,
+                quote! { __metastruct_ctx },
+                quote! {
+                    #coercion
+                    #call;
+                },
+            )
         })
         .collect::<Vec<_>>();
 
     quote! {
         #[macro_export]
         macro_rules! #macro_name {
-            ($left:expr, $right:expr, $f:expr) => {
+            ($left:expr, $right:expr, #ctx_param $f:expr) => {
                 match ($left, $right) {
                     (#left_type_name {
                         #(
@@ -165,10 +668,10 @@ pub(crate) fn generate_bimapping_macro(
                         )*
                         ..
                     }) => {
+                        #ctx_binding
                         let mut __metastruct_i: usize = 0;
                         #(
-                            let __metastruct_f: #mapping_function_types = &mut $f;
-                            #function_call_exprs;
+                            #field_visits
                             __metastruct_i += 1;
                         )*
                     }
@@ -176,5 +679,4 @@ pub(crate) fn generate_bimapping_macro(
             }
         }
     }
-    .into()
 }
@@ -1,9 +1,17 @@
-use crate::{exclude::calculate_excluded_fields, FieldOpts, NumFieldsOpts};
-use proc_macro::TokenStream;
+use crate::{
+    cfg::{cfg_count, cfg_errors, generate_for_each_variant},
+    exclude::{
+        calculate_field_predicates, calculate_selected_field_opts, calculate_selected_fields,
+    },
+    FieldOpts, NumFieldsOpts,
+};
+use darling::Error;
+use proc_macro2::TokenStream;
 use quote::quote;
 use syn::{Ident, ImplGenerics, Type, TypeGenerics, WhereClause};
 
 pub(crate) fn generate_num_fields_impl(
+    num_fields_name: &Ident,
     type_name: &Ident,
     (impl_generics, ty_generics, where_clause): &(ImplGenerics, TypeGenerics, Option<&WhereClause>),
     fields: &[(Ident, Type)],
@@ -16,18 +24,103 @@ pub(crate) fn generate_num_fields_impl(
         (quote! { metastruct::selectors::AllFields }, None)
     };
 
-    let excluded_fields = calculate_excluded_fields(
-        &num_fields_opts.exclude,
-        &num_fields_opts.groups,
+    // Each selected field contributes one to the count and its type to the list of types, unless
+    // it is flattened, in which case it contributes the number and types of the fields selected by
+    // its own implementations.
+    let selected_fields = calculate_selected_fields(&num_fields_opts.selection, fields, field_opts);
+    let selected_field_opts =
+        calculate_selected_field_opts(&num_fields_opts.selection, fields, field_opts);
+    let gates = selected_field_opts
+        .iter()
+        .map(|field_opts| &field_opts.cfg)
+        .collect::<Vec<_>>();
+    let (field_counts, field_types): (Vec<_>, Vec<_>) = selected_fields
+        .iter()
+        .zip(&selected_field_opts)
+        .map(|((_, field_type), field_opts)| {
+            if let Some(inner_selector) = field_opts.flatten.get(num_fields_name) {
+                (
+                    quote! { <#field_type as metastruct::NumFields<#inner_selector>>::NUM_FIELDS },
+                    quote! { <#field_type as metastruct::FieldTypes<#inner_selector>>::Types },
+                )
+            } else {
+                (quote! { 1 }, quote! { #field_type })
+            }
+        })
+        .unzip();
+
+    // Runtime count for structs with conditional fields.
+    let predicates = calculate_field_predicates(
+        num_fields_name,
+        &num_fields_opts.selection,
         fields,
         field_opts,
     );
-    let num_fields = fields
+    let conditional = gates
         .iter()
-        .filter(|(field_name, _)| !excluded_fields.contains(&field_name))
-        .count();
+        .zip(&predicates)
+        .filter(|(_, predicates)| !predicates.is_empty())
+        .map(|(gate, _)| *gate)
+        .collect::<Vec<_>>();
+    let mut errors = vec![];
+    let context_impl = if conditional.is_empty() {
+        None
+    } else if let Some(context_ty) = &num_fields_opts.context {
+        let num_fields = cfg_count(gates.iter().zip(&predicates).zip(&field_counts).map(
+            |((gate, predicates), count)| {
+                if predicates.is_empty() {
+                    (*gate, quote! { #count })
+                } else {
+                    (
+                        *gate,
+                        quote! { if #(#predicates(context))&&* { #count } else { 0 } },
+                    )
+                }
+            },
+        ));
+        Some(quote! {
+            impl #impl_generics metastruct::NumFieldsWithContext<#selector_ty, #context_ty>
+            for #type_name #ty_generics
+            #where_clause
+            {
+                fn num_fields(context: &#context_ty) -> usize {
+                    #num_fields
+                }
+            }
+        })
+    } else {
+        // The error is only reported in the configurations in which a conditional field exists.
+        errors.push((
+            conditional,
+            Error::custom(format!(
+                "num_fields `{num_fields_name}` has conditional fields and requires a `context`"
+            ))
+            .with_span(num_fields_name),
+        ));
+        None
+    };
 
-    quote! {
+    // Tuple types can't have conditional elements, so the list of types is implemented for each
+    // combination of the predicates of the selected fields.
+    let field_types_impl = num_fields_opts.field_types.then(|| {
+        generate_for_each_variant(&gates, |variant| {
+            let field_types = gates
+                .iter()
+                .zip(&field_types)
+                .filter(|(gate, _)| gate.holds_in(variant))
+                .map(|(_, field_type)| field_type);
+            quote! {
+                impl #impl_generics metastruct::FieldTypes<#selector_ty> for #type_name #ty_generics
+                #where_clause
+                {
+                    type Types = (#(#field_types,)*);
+                }
+            }
+        })
+    });
+    let num_fields = cfg_count(gates.iter().copied().zip(field_counts));
+
+    let items = quote! {
         #selector_ty_def
 
         impl #impl_generics metastruct::NumFields<#selector_ty> for #type_name #ty_generics
@@ -35,6 +128,10 @@ pub(crate) fn generate_num_fields_impl(
         {
             const NUM_FIELDS: usize = #num_fields;
         }
-    }
-    .into()
+
+        #field_types_impl
+
+        #context_impl
+    };
+    cfg_errors(errors, items)
 }
@@ -0,0 +1,82 @@
+use crate::{
+    exclude::{calculate_selected_field_opts, calculate_selected_fields},
+    FieldOpts, StringAccessOpts,
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Ident, ItemStruct, Type};
+
+pub(crate) fn generate_string_access(
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    string_access_opts: &StringAccessOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    let (selected_fields, selected_field_types): (Vec<_>, Vec<_>) =
+        calculate_selected_fields(&string_access_opts.selection, fields, field_opts)
+            .into_iter()
+            .cloned()
+            .unzip();
+    let names = selected_fields
+        .iter()
+        .map(|field_name| field_name.to_string())
+        .collect::<Vec<_>>();
+    let cfgs = calculate_selected_field_opts(&string_access_opts.selection, fields, field_opts)
+        .into_iter()
+        .map(|field_opts| field_opts.cfg.attr())
+        .collect::<Vec<_>>();
+
+    quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            /// Parse `value` using the `FromStr` implementation of the named field's type, and
+            /// assign it to the field.
+            #vis fn set_field_from_str(
+                &mut self,
+                name: &str,
+                value: &str,
+            ) -> ::core::result::Result<(), metastruct::SetFieldError> {
+                match name {
+                    #(
+                        #cfgs
+                        #names => {
+                            self.#selected_fields = value
+                                .parse::<#selected_field_types>()
+                                .map_err(|error| metastruct::SetFieldError::InvalidValue {
+                                    name: #names,
+                                    value: value.to_string(),
+                                    error: error.to_string(),
+                                })?;
+                            ::core::result::Result::Ok(())
+                        }
+                    )*
+                    _ => ::core::result::Result::Err(metastruct::UnknownFieldError {
+                        name: name.to_string(),
+                        valid_names: &[#(#cfgs #names),*],
+                    }
+                    .into()),
+                }
+            }
+
+            /// Format the named field using its `Display` implementation.
+            #vis fn field_to_string(
+                &self,
+                name: &str,
+            ) -> ::core::result::Result<::std::string::String, metastruct::UnknownFieldError> {
+                match name {
+                    #(
+                        #cfgs
+                        #names => ::core::result::Result::Ok(self.#selected_fields.to_string()),
+                    )*
+                    _ => ::core::result::Result::Err(metastruct::UnknownFieldError {
+                        name: name.to_string(),
+                        valid_names: &[#(#cfgs #names),*],
+                    }),
+                }
+            }
+        }
+    }
+}
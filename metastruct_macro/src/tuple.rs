@@ -0,0 +1,144 @@
+use crate::{
+    cfg::generate_for_each_variant, exclude::calculate_selection, names::upper_camel_case,
+    FieldOpts, TupleOpts,
+};
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{GenericParam, Ident, ItemStruct, Type};
+
+pub(crate) fn generate_tuple(
+    key: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    tuple_opts: &TupleOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let alias = format_ident!("{type_name}{}", upper_camel_case(key));
+    let into_tuple = format_ident!("into_{key}");
+    let from_tuple = format_ident!("from_{key}");
+    let as_tuple_ref = format_ident!("as_{key}_ref");
+    let as_tuple_mut = format_ident!("as_{key}_mut");
+
+    let alias_doc = format!("The `{key}` fields of [`{type_name}`], as a tuple.");
+    let into_doc = format!("Move the `{key}` fields into a tuple, discarding any other fields.");
+    let from_doc = format!(
+        "Construct [`{type_name}`] from a tuple of its `{key}` fields. Other fields take their \
+         default values."
+    );
+    let as_ref_doc = format!("Tuple of references to the `{key}` fields.");
+    let as_mut_doc = format!("Tuple of mutable references to the `{key}` fields.");
+
+    let selection = calculate_selection(&tuple_opts.selection, fields, field_opts);
+    let selected_gates = field_opts
+        .iter()
+        .zip(&selection)
+        .filter_map(|(field_opts, selected)| selected.then_some(&field_opts.cfg))
+        .collect::<Vec<_>>();
+
+    // Tuples can't have conditional elements, so the items are generated for each combination of
+    // the predicates of the selected fields.
+    generate_for_each_variant(&selected_gates, |variant| {
+        let (selected_fields, selected_field_types): (Vec<_>, Vec<_>) = fields
+            .iter()
+            .zip(field_opts)
+            .zip(&selection)
+            .filter(|((_, field_opts), selected)| **selected && field_opts.cfg.holds_in(variant))
+            .map(|((field, _), _)| field.clone())
+            .unzip();
+
+        // Type aliases may not have unused parameters, so only keep those used by the fields.
+        let alias_params = item
+            .generics
+            .params
+            .iter()
+            .filter_map(|param| {
+                let (ident, param, arg) = match param {
+                    GenericParam::Lifetime(param) => {
+                        let lifetime = &param.lifetime;
+                        (&lifetime.ident, quote! { #lifetime }, quote! { #lifetime })
+                    }
+                    GenericParam::Type(param) => {
+                        let ident = &param.ident;
+                        (ident, quote! { #ident }, quote! { #ident })
+                    }
+                    GenericParam::Const(param) => {
+                        let (ident, ty) = (&param.ident, &param.ty);
+                        (ident, quote! { const #ident: #ty }, quote! { #ident })
+                    }
+                };
+                selected_field_types
+                    .iter()
+                    .any(|ty| mentions_ident(ty.to_token_stream(), ident))
+                    .then_some((param, arg))
+            })
+            .collect::<Vec<_>>();
+        let (alias_params, alias_generics) = if alias_params.is_empty() {
+            (None, None)
+        } else {
+            let (params, args): (Vec<_>, Vec<_>) = alias_params.into_iter().unzip();
+            (
+                Some(quote! { <#(#params),*> }),
+                Some(quote! { <#(#args),*> }),
+            )
+        };
+
+        // Fields which aren't selected take their default value, in the configurations in which
+        // they exist.
+        let field_values = fields.iter().zip(field_opts).zip(&selection).filter_map(
+            |(((field_name, _), field_opts), selected)| {
+                let cfg = field_opts.cfg.attr();
+                if *selected {
+                    field_opts
+                        .cfg
+                        .holds_in(variant)
+                        .then(|| quote! { #field_name })
+                } else if let Some(default) = &field_opts.default {
+                    Some(quote! { #cfg #field_name: #default })
+                } else {
+                    Some(quote! { #cfg #field_name: ::core::default::Default::default() })
+                }
+            },
+        );
+
+        quote! {
+            #[doc = #alias_doc]
+            #vis type #alias #alias_params = (#(#selected_field_types,)*);
+
+            impl #impl_generics #type_name #ty_generics #where_clause {
+                #[doc = #into_doc]
+                #vis fn #into_tuple(self) -> #alias #alias_generics {
+                    (#(self.#selected_fields,)*)
+                }
+
+                #[doc = #from_doc]
+                #vis fn #from_tuple((#(#selected_fields,)*): #alias #alias_generics) -> Self {
+                    Self {
+                        #(#field_values,)*
+                    }
+                }
+
+                #[doc = #as_ref_doc]
+                #vis fn #as_tuple_ref(&self) -> (#(&#selected_field_types,)*) {
+                    (#(&self.#selected_fields,)*)
+                }
+
+                #[doc = #as_mut_doc]
+                #vis fn #as_tuple_mut(&mut self) -> (#(&mut #selected_field_types,)*) {
+                    (#(&mut self.#selected_fields,)*)
+                }
+            }
+        }
+    })
+}
+
+/// Whether `tokens` contain the identifier `ident`, including as the name of a lifetime.
+fn mentions_ident(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(token_ident) => token_ident == *ident,
+        TokenTree::Group(group) => mentions_ident(group.stream(), ident),
+        _ => false,
+    })
+}
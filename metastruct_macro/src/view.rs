@@ -0,0 +1,486 @@
+use crate::{
+    cfg::cfg_errors,
+    exclude::{calculate_selected_field_opts, calculate_selected_fields, calculate_selection},
+    names::upper_camel_case,
+    FieldOpts, PartitionOpts, SplitOpts, ViewOpts,
+};
+use darling::Error;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use std::collections::HashMap;
+use syn::{GenericParam, Generics, Ident, ItemStruct, Lifetime, LifetimeParam, Type};
+
+/// Names of the items generated for a view.
+pub(crate) struct ViewNames {
+    pub ref_type: Ident,
+    pub mut_type: Ident,
+    pub owned_type: Ident,
+}
+
+impl ViewNames {
+    pub fn new(type_name: &Ident, view_name: &Ident) -> Self {
+        let camel_case = upper_camel_case(view_name);
+        Self {
+            ref_type: format_ident!("{type_name}{camel_case}Ref"),
+            mut_type: format_ident!("{type_name}{camel_case}Mut"),
+            owned_type: format_ident!("{type_name}{camel_case}"),
+        }
+    }
+}
+
+/// Choose a lifetime name that doesn't clash with any of the struct's own lifetimes.
+pub(crate) fn fresh_lifetime(generics: &Generics) -> Lifetime {
+    let is_taken = |name: &str| {
+        generics
+            .lifetimes()
+            .any(|param| param.lifetime.ident == name)
+    };
+    let name = (0..)
+        .map(|i| {
+            if i == 0 {
+                "a".to_string()
+            } else {
+                format!("a{i}")
+            }
+        })
+        .find(|name| !is_taken(name))
+        .expect("infinite iterator");
+    Lifetime::new(&format!("'{name}"), proc_macro2::Span::call_site())
+}
+
+/// Add `lifetime` as the first parameter of `generics`.
+pub(crate) fn with_lifetime(generics: &Generics, lifetime: &Lifetime) -> Generics {
+    let mut generics = generics.clone();
+    generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())),
+    );
+    generics
+}
+
+/// The generics of a view borrowed from `self` with an elided lifetime.
+fn elided_generics(generics: &Generics) -> Generics {
+    with_lifetime(
+        generics,
+        &Lifetime::new("'_", proc_macro2::Span::call_site()),
+    )
+}
+
+/// Whether the borrowed views of a selection need a marker field.
+///
+/// The marker uses the struct's generics, which the selected fields may not use, and the view's
+/// lifetime, which is unused if none of the selected fields exist in some configuration.
+fn needs_phantom(generics: &Generics, selected_field_opts: &[&FieldOpts]) -> bool {
+    !generics.params.is_empty()
+        || !selected_field_opts
+            .iter()
+            .any(|field_opts| field_opts.cfg.is_unconditional())
+}
+
+/// Names and options of the fields selected by `selection`, from `calculate_selection`.
+fn selected_entries<'a>(
+    fields: &'a [(Ident, Type)],
+    field_opts: &'a [FieldOpts],
+    selection: &[bool],
+) -> (Vec<&'a Ident>, Vec<&'a FieldOpts>) {
+    fields
+        .iter()
+        .zip(field_opts)
+        .zip(selection)
+        .filter(|(_, selected)| **selected)
+        .map(|(((field_name, _), field_opts), _)| (field_name, field_opts))
+        .unzip()
+}
+
+/// The `cfg` attribute of each of the fields with the given options.
+fn cfg_attrs(field_opts: &[&FieldOpts]) -> Vec<Option<TokenStream>> {
+    field_opts
+        .iter()
+        .map(|field_opts| field_opts.cfg.attr())
+        .collect()
+}
+
+/// Initializer for the marker field of views, if they have one.
+fn phantom_init(phantom: bool) -> Option<TokenStream> {
+    phantom.then(|| quote! { __metastruct_phantom: ::core::marker::PhantomData, })
+}
+
+pub(crate) fn generate_views(
+    view_name: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    view_opts: &ViewOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let ViewNames {
+        ref_type,
+        mut_type,
+        owned_type,
+    } = ViewNames::new(type_name, view_name);
+    let ref_method = format_ident!("as_{view_name}_ref");
+    let mut_method = format_ident!("as_{view_name}_mut");
+
+    let (selected_fields, selected_field_types): (Vec<_>, Vec<_>) =
+        calculate_selected_fields(&view_opts.selection, fields, field_opts)
+            .into_iter()
+            .cloned()
+            .unzip();
+    let selected_field_opts =
+        calculate_selected_field_opts(&view_opts.selection, fields, field_opts);
+    let selected_field_vis = selected_field_opts
+        .iter()
+        .map(|field_opts| &field_opts.vis)
+        .collect::<Vec<_>>();
+    let cfgs = cfg_attrs(&selected_field_opts);
+
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let lifetime = fresh_lifetime(&item.generics);
+    let view_generics = with_lifetime(&item.generics, &lifetime);
+    let elided_generics = elided_generics(&item.generics);
+    let (_, view_ty_generics, _) = elided_generics.split_for_impl();
+    let view_where_clause = &view_generics.where_clause;
+
+    let phantom = needs_phantom(&item.generics, &selected_field_opts);
+    let phantom_field = phantom.then(|| {
+        quote! {
+            #[doc(hidden)]
+            pub __metastruct_phantom: ::core::marker::PhantomData<&#lifetime #type_name #ty_generics>,
+        }
+    });
+    let phantom_init = phantom_init(phantom);
+
+    // An owned struct, moved in and out of the original struct by partitions.
+    let owned_struct = view_opts.owned.then(|| {
+        let generics = &item.generics;
+        let phantom_field = (!generics.params.is_empty()).then(|| {
+            quote! {
+                #[doc(hidden)]
+                pub __metastruct_phantom: ::core::marker::PhantomData<#type_name #ty_generics>,
+            }
+        });
+        let doc = format!("The `{view_name}` fields of [`{type_name}`], moved out of it.");
+        quote! {
+            #[doc = #doc]
+            #[allow(dead_code)]
+            #vis struct #owned_type #generics #where_clause {
+                #(
+                    #cfgs
+                    #selected_field_vis #selected_fields: #selected_field_types,
+                )*
+                #phantom_field
+            }
+        }
+    });
+
+    // Usually only one of the shared and mutable views of a selection is used.
+    let ref_doc = format!("Shared references to the `{view_name}` fields of [`{type_name}`].");
+    let mut_doc = format!("Mutable references to the `{view_name}` fields of [`{type_name}`].");
+
+    quote! {
+        #owned_struct
+
+        #[doc = #ref_doc]
+        #[allow(dead_code)]
+        #vis struct #ref_type #view_generics #view_where_clause {
+            #(
+                #cfgs
+                #selected_field_vis #selected_fields: &#lifetime #selected_field_types,
+            )*
+            #phantom_field
+        }
+
+        #[doc = #mut_doc]
+        #[allow(dead_code)]
+        #vis struct #mut_type #view_generics #view_where_clause {
+            #(
+                #cfgs
+                #selected_field_vis #selected_fields: &#lifetime mut #selected_field_types,
+            )*
+            #phantom_field
+        }
+
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #ref_doc]
+            #vis fn #ref_method(&self) -> #ref_type #view_ty_generics {
+                #ref_type {
+                    #(
+                        #cfgs
+                        #selected_fields: &self.#selected_fields,
+                    )*
+                    #phantom_init
+                }
+            }
+
+            #[doc = #mut_doc]
+            #vis fn #mut_method(&mut self) -> #mut_type #view_ty_generics {
+                let #type_name {
+                    #(
+                        #cfgs
+                        #selected_fields,
+                    )*
+                    ..
+                } = self;
+                #mut_type {
+                    #(
+                        #cfgs
+                        #selected_fields,
+                    )*
+                    #phantom_init
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn generate_split(
+    split_name: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    views: &HashMap<Ident, ViewOpts>,
+    split_opts: &SplitOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let method_name = format_ident!("split_{split_name}_mut");
+
+    let is_mutable = |view_name: &Ident| {
+        split_opts
+            .mutable
+            .as_ref()
+            .is_some_and(|mutable| mutable.idents.contains(view_name))
+    };
+    if let Some(view_name) = split_opts
+        .mutable
+        .iter()
+        .flat_map(|mutable| &mutable.idents)
+        .find(|view_name| !split_opts.views.idents.contains(view_name))
+    {
+        return Error::custom(format!(
+            "mutable view `{view_name}` is not one of the views of split `{split_name}`"
+        ))
+        .with_span(view_name)
+        .write_errors();
+    }
+
+    // Fields selected by each view, in the order the views are listed.
+    let mut view_selections = vec![];
+    for view_name in &split_opts.views.idents {
+        let Some(view_opts) = views.get(view_name) else {
+            return Error::custom(format!("unknown view `{view_name}`"))
+                .with_span(view_name)
+                .write_errors();
+        };
+        let selection = calculate_selection(&view_opts.selection, fields, field_opts);
+        view_selections.push((view_name, selection));
+    }
+
+    // A field may only be shared between views that borrow it immutably. Fields are compared by
+    // entry, so that the error is only reported in the configurations where the entry exists.
+    let mut errors = vec![];
+    for (i, (view_name, selection)) in view_selections.iter().enumerate() {
+        for (other_view_name, other_selection) in &view_selections[i + 1..] {
+            if !is_mutable(view_name) && !is_mutable(other_view_name) {
+                continue;
+            }
+            for (((field_name, _), field_opts), _) in fields
+                .iter()
+                .zip(field_opts)
+                .zip(selection.iter().zip(other_selection))
+                .filter(|(_, (selected, other_selected))| **selected && **other_selected)
+            {
+                errors.push((
+                    vec![&field_opts.cfg],
+                    Error::custom(format!(
+                        "views `{view_name}` and `{other_view_name}` of split `{split_name}` \
+                         both select field `{field_name}`, which is borrowed mutably"
+                    ))
+                    .with_span(split_name),
+                ));
+            }
+        }
+    }
+
+    let borrowed = (0..fields.len())
+        .map(|i| view_selections.iter().any(|(_, selection)| selection[i]))
+        .collect::<Vec<_>>();
+    let (borrowed_fields, borrowed_field_opts) = selected_entries(fields, field_opts, &borrowed);
+    let borrowed_cfgs = cfg_attrs(&borrowed_field_opts);
+
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let elided_generics = elided_generics(&item.generics);
+    let (_, view_ty_generics, _) = elided_generics.split_for_impl();
+
+    let (view_types, view_inits): (Vec<_>, Vec<_>) = view_selections
+        .iter()
+        .map(|(view_name, selection)| {
+            let ViewNames {
+                ref_type, mut_type, ..
+            } = ViewNames::new(type_name, view_name);
+            let (selected_fields, selected_field_opts) =
+                selected_entries(fields, field_opts, selection);
+            let cfgs = cfg_attrs(&selected_field_opts);
+            let phantom_init = phantom_init(needs_phantom(&item.generics, &selected_field_opts));
+            if is_mutable(view_name) {
+                (
+                    quote! { #mut_type #view_ty_generics },
+                    quote! { #mut_type { #(#cfgs #selected_fields,)* #phantom_init } },
+                )
+            } else {
+                (
+                    quote! { #ref_type #view_ty_generics },
+                    quote! {
+                        #ref_type {
+                            #(#cfgs #selected_fields: &*#selected_fields,)*
+                            #phantom_init
+                        }
+                    },
+                )
+            }
+        })
+        .unzip();
+
+    let doc = format!(
+        "Borrow the fields of [`{type_name}`] as the disjoint views of split `{split_name}`."
+    );
+
+    let items = quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #doc]
+            #vis fn #method_name(&mut self) -> (#(#view_types,)*) {
+                let #type_name {
+                    #(
+                        #borrowed_cfgs
+                        #borrowed_fields,
+                    )*
+                    ..
+                } = self;
+                (#(#view_inits,)*)
+            }
+        }
+    };
+    cfg_errors(errors, items)
+}
+
+pub(crate) fn generate_partition(
+    partition_name: &Ident,
+    item: &ItemStruct,
+    fields: &[(Ident, Type)],
+    field_opts: &[FieldOpts],
+    views: &HashMap<Ident, ViewOpts>,
+    partition_opts: &PartitionOpts,
+) -> TokenStream {
+    let type_name = &item.ident;
+    let vis = &item.vis;
+    let into_method = format_ident!("into_{partition_name}");
+    let from_method = format_ident!("from_{partition_name}");
+
+    // Fields selected by each view, in the order the views are listed.
+    let mut view_selections = vec![];
+    for view_name in &partition_opts.views.idents {
+        let Some(view_opts) = views.get(view_name) else {
+            return Error::custom(format!("unknown view `{view_name}`"))
+                .with_span(view_name)
+                .write_errors();
+        };
+        if !view_opts.owned {
+            return Error::custom(format!(
+                "view `{view_name}` must be `owned` to be part of partition `{partition_name}`"
+            ))
+            .with_span(view_name)
+            .write_errors();
+        }
+        let selection = calculate_selection(&view_opts.selection, fields, field_opts);
+        view_selections.push((view_name, selection));
+    }
+
+    // Every field must be moved into exactly one view, in the configurations where it exists.
+    let mut errors = vec![];
+    for (i, ((field_name, _), field_opts)) in fields.iter().zip(field_opts).enumerate() {
+        let count = view_selections
+            .iter()
+            .filter(|(_, selection)| selection[i])
+            .count();
+        if count != 1 {
+            let problem = if count == 0 {
+                "is not selected by any view"
+            } else {
+                "is selected by more than one view"
+            };
+            errors.push((
+                vec![&field_opts.cfg],
+                Error::custom(format!(
+                    "views of partition `{partition_name}` must partition the fields of \
+                     `{type_name}`, but field `{field_name}` {problem}"
+                ))
+                .with_span(partition_name),
+            ));
+        }
+    }
+
+    let field_names = fields
+        .iter()
+        .map(|(field_name, _)| field_name)
+        .collect::<Vec<_>>();
+    let cfgs = cfg_attrs(&field_opts.iter().collect::<Vec<_>>());
+
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    // Owned views have no lifetime, and only need a marker for the struct's generics.
+    let phantom_init = phantom_init(!item.generics.params.is_empty());
+
+    let view_types = view_selections
+        .iter()
+        .map(|(view_name, _)| ViewNames::new(type_name, view_name).owned_type)
+        .collect::<Vec<_>>();
+    let (view_fields, view_cfgs): (Vec<_>, Vec<_>) = view_selections
+        .iter()
+        .map(|(_, selection)| {
+            let (selected_fields, selected_field_opts) =
+                selected_entries(fields, field_opts, selection);
+            (selected_fields, cfg_attrs(&selected_field_opts))
+        })
+        .unzip();
+
+    let into_doc = format!(
+        "Move the fields of [`{type_name}`] into the views of partition `{partition_name}`."
+    );
+    let from_doc =
+        format!("Reassemble [`{type_name}`] from the views of partition `{partition_name}`.");
+
+    let items = quote! {
+        impl #impl_generics #type_name #ty_generics #where_clause {
+            #[doc = #into_doc]
+            #vis fn #into_method(self) -> (#(#view_types #ty_generics,)*) {
+                let #type_name {
+                    #(
+                        #cfgs
+                        #field_names,
+                    )*
+                } = self;
+                (#(
+                    #view_types {
+                        #(#view_cfgs #view_fields,)*
+                        #phantom_init
+                    },
+                )*)
+            }
+
+            #[doc = #from_doc]
+            #vis fn #from_method(
+                #(
+                    #view_types { #(#view_cfgs #view_fields,)* .. }: #view_types #ty_generics
+                ),*
+            ) -> Self {
+                #type_name {
+                    #(
+                        #cfgs
+                        #field_names,
+                    )*
+                }
+            }
+        }
+    };
+    cfg_errors(errors, items)
+}
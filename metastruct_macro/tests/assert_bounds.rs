@@ -0,0 +1,57 @@
+use metastruct_macro::metastruct;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+#[metastruct(
+    mappings(map_hashed_fields(assert_bounds = "Hash + Clone", exclude(ratio))),
+    bimappings(zip_foo(other_type = "Foo", assert_bounds = "PartialEq", exclude(ratio))),
+    group_bounds(hashing = "Hash + Eq")
+)]
+#[derive(Clone)]
+pub struct Foo {
+    #[metastruct(groups(hashing))]
+    pub id: u64,
+    #[metastruct(groups(hashing))]
+    pub name: String,
+    pub value: char,
+    pub ratio: f64,
+}
+
+#[test]
+fn mapping_with_asserted_bounds() {
+    let foo = Foo {
+        id: 1,
+        name: "a".to_string(),
+        value: 'x',
+        ratio: 0.5,
+    };
+    let mut hasher = DefaultHasher::new();
+    map_hashed_fields!(&foo, |_, field| field.clone().hash(&mut hasher));
+    let mut count = 0;
+    zip_foo!(&foo, &foo.clone(), |_, a, b| {
+        assert!(a == b);
+        count += 1;
+    });
+    assert_eq!(count, 3);
+    assert_ne!(hasher.finish(), 0);
+}
+
+#[metastruct(group_bounds(keys = "Ord + Hash", all = "Clone"))]
+pub struct Index<K, V>
+where
+    K: Ord + Hash + Clone,
+    V: Clone,
+{
+    #[metastruct(groups(keys))]
+    pub key: K,
+    pub values: Vec<V>,
+}
+
+#[test]
+fn generic_group_bounds() {
+    let index = Index {
+        key: "k",
+        values: vec![1],
+    };
+    assert_eq!((index.key, index.values), ("k", vec![1]));
+}
@@ -0,0 +1,98 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(dispatch(
+    map_node_at(),
+    map_node_at_mut(mutable),
+    map_node_counter_at(only_types(u64)),
+    try_map_node_at(fallible),
+))]
+pub struct Node {
+    pub slot: u64,
+    pub name: String,
+    pub epoch: u64,
+}
+
+fn node() -> Node {
+    Node {
+        slot: 10,
+        name: "node".to_string(),
+        epoch: 2,
+    }
+}
+
+#[test]
+fn single_field() {
+    let node = node();
+    assert_eq!(
+        map_node_at!(&node, 1, |field| field.to_string()),
+        Some("node".to_string())
+    );
+    assert_eq!(map_node_at!(&node, 3, |field| field.to_string()), None);
+    assert_eq!(map_node_counter_at!(&node, 1, |x| *x), Some(2));
+}
+
+#[test]
+fn dirty_fields() {
+    let mut node = node();
+    let dirty = [true, false, true];
+    for (i, _) in dirty.iter().enumerate().filter(|(_, dirty)| **dirty) {
+        map_node_at_mut!(&mut node, i, |field| {
+            let _ = std::mem::take(field);
+        });
+    }
+    assert_eq!((node.slot, node.name.as_str(), node.epoch), (0, "node", 0));
+}
+
+fn checked_len(node: &Node, i: usize) -> Result<Option<usize>, String> {
+    Ok(try_map_node_at!(node, i, |field| {
+        let len = field.to_string().len();
+        if len > 3 {
+            Err(format!("field {i} too long"))
+        } else {
+            Ok(len)
+        }
+    }))
+}
+
+#[test]
+fn fallible() {
+    let node = node();
+    assert_eq!(checked_len(&node, 0), Ok(Some(2)));
+    assert_eq!(checked_len(&node, 1), Err("field 1 too long".to_string()));
+    assert_eq!(checked_len(&node, 5), Ok(None));
+}
+
+pub struct Config {
+    pub version: u8,
+}
+
+fn is_v2(config: &Config) -> bool {
+    config.version >= 2
+}
+
+#[metastruct(
+    mappings(map_versioned(groups(versioned))),
+    dispatch(map_versioned_at(groups(versioned)), map_slot_at(include(slot)))
+)]
+pub struct Versioned {
+    pub slot: u64,
+    #[metastruct(only_if(versioned = is_v2))]
+    pub epoch: u64,
+}
+
+#[test]
+fn conditional_field() {
+    let versioned = Versioned { slot: 1, epoch: 2 };
+    let v1 = Config { version: 1 };
+    let v2 = Config { version: 2 };
+
+    let mut visited = vec![];
+    map_versioned!(&versioned, &v1, |i, x| visited.push((i, *x)));
+    assert_eq!(visited, vec![(0, 1)]);
+    assert_eq!(map_versioned_at!(&versioned, 0, &v1, |x| *x), Some(1));
+    assert_eq!(map_versioned_at!(&versioned, 1, &v1, |x| *x), None);
+    assert_eq!(map_versioned_at!(&versioned, 1, &v2, |x| *x), Some(2));
+
+    // Dispatch macros without conditional fields don't take a context.
+    assert_eq!(map_slot_at!(&versioned, 0, |x| *x), Some(1));
+}
@@ -0,0 +1,144 @@
+use metastruct_macro::metastruct;
+use std::fmt::Debug;
+
+#[metastruct(dyn_access(
+    field(),
+    debug_field(trait = "Debug", exclude(secret)),
+    display_field(trait = "std::fmt::Display + Send", only_types(u64, String)),
+))]
+pub struct Node {
+    pub id: u64,
+    pub name: String,
+    pub peers: Vec<u64>,
+    secret: [u8; 4],
+}
+
+fn node() -> Node {
+    Node {
+        id: 7,
+        name: "node".to_string(),
+        peers: vec![1, 2],
+        secret: [0; 4],
+    }
+}
+
+#[test]
+fn any_by_name() {
+    let mut node = node();
+    assert_eq!(
+        node.get_field("id").and_then(|id| id.downcast_ref::<u64>()),
+        Some(&7)
+    );
+    assert!(node
+        .get_field("id")
+        .unwrap()
+        .downcast_ref::<u32>()
+        .is_none());
+    assert!(node.get_field("missing").is_none());
+
+    let peers = node.get_field_mut("peers").unwrap();
+    peers.downcast_mut::<Vec<u64>>().unwrap().push(3);
+    assert_eq!(node.peers, vec![1, 2, 3]);
+    assert!(node.get_field("secret").is_some());
+}
+
+#[test]
+fn any_by_index() {
+    let mut node = node();
+    *node
+        .get_field_by_index_mut(1)
+        .and_then(|name| name.downcast_mut::<String>())
+        .unwrap() = "renamed".to_string();
+    assert_eq!(node.name, "renamed");
+    assert!(node.get_field_by_index(3).is_some());
+    assert!(node.get_field_by_index(4).is_none());
+}
+
+#[test]
+fn trait_objects() {
+    let node = node();
+    let debug = |name| format!("{:?}", node.get_debug_field(name).unwrap());
+    assert_eq!(debug("peers"), "[1, 2]");
+    assert_eq!(debug("name"), "\"node\"");
+    assert!(node.get_debug_field("secret").is_none());
+
+    let displayed = (0..)
+        .map_while(|i| node.get_display_field_by_index(i))
+        .map(|field| field.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(displayed, vec!["7", "node"]);
+}
+
+#[metastruct(dyn_access(value(trait = "Debug")))]
+pub struct Wrapper<T: Debug> {
+    pub inner: T,
+    pub count: usize,
+}
+
+#[test]
+fn generic_fields() {
+    let mut wrapper = Wrapper {
+        inner: Some("x"),
+        count: 1,
+    };
+    assert_eq!(
+        format!("{:?}", wrapper.get_value("inner").unwrap()),
+        "Some(\"x\")"
+    );
+    assert_eq!(
+        format!("{:?}", wrapper.get_value_by_index_mut(1).unwrap()),
+        "1"
+    );
+}
+
+#[metastruct(dyn_iter(fields_dyn(trait = "Debug"), any_fields(exclude(secret)),))]
+pub struct Peer {
+    pub id: u64,
+    pub addr: String,
+    secret: [u8; 2],
+}
+
+/// Generic dump code, written once for any iterator of named trait objects.
+fn dump<'a>(fields: impl Iterator<Item = (&'static str, &'a dyn Debug)>) -> String {
+    fields
+        .map(|(name, value)| format!("{name}={value:?}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn trait_object_iterator() {
+    let peer = Peer {
+        id: 1,
+        addr: "127.0.0.1".to_string(),
+        secret: [1, 2],
+    };
+    assert_eq!(
+        dump(peer.fields_dyn()),
+        "id=1 addr=\"127.0.0.1\" secret=[1, 2]"
+    );
+    assert_eq!(peer.fields_dyn().len(), 3);
+    assert_eq!(
+        peer.fields_dyn().next_back().map(|(name, _)| name),
+        Some("secret")
+    );
+}
+
+#[test]
+fn mutable_trait_object_iterator() {
+    let mut peer = Peer {
+        id: 1,
+        addr: "127.0.0.1".to_string(),
+        secret: [1, 2],
+    };
+    for (name, value) in peer.any_fields_mut() {
+        if let Some(id) = value.downcast_mut::<u64>() {
+            *id += 1;
+        } else {
+            assert_eq!(name, "addr");
+        }
+    }
+    assert_eq!(peer.id, 2);
+    assert_eq!(peer.any_fields().count(), 2);
+    assert_eq!(peer.secret, [1, 2]);
+}
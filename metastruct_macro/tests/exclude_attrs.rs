@@ -0,0 +1,63 @@
+use metastruct_macro::metastruct;
+use serde::{Deserialize, Serialize};
+
+#[metastruct(mappings(
+    map_serialized(exclude_attrs(serde(skip))),
+    map_not_serde(exclude_attrs(serde)),
+    map_no_defaults(exclude_attrs(serde(default))),
+    map_visible(exclude_attrs(serde(skip), doc(hidden))),
+))]
+#[derive(Serialize, Deserialize)]
+pub struct Foo {
+    a: u64,
+    #[serde(skip)]
+    b: u64,
+    #[serde(rename = "cee", skip)]
+    c: u64,
+    #[serde(default = "default_d")]
+    d: u64,
+    #[doc(hidden)]
+    e: u64,
+}
+
+fn default_d() -> u64 {
+    0
+}
+
+fn foo() -> Foo {
+    Foo {
+        a: 1,
+        b: 10,
+        c: 100,
+        d: 1000,
+        e: 10000,
+    }
+}
+
+macro_rules! sum {
+    ($mapping:ident, $foo:expr) => {{
+        let mut total = 0;
+        $mapping!($foo, |_, x| total += *x);
+        total
+    }};
+}
+
+#[test]
+fn exclude_nested_meta() {
+    assert_eq!(sum!(map_serialized, &foo()), 11001);
+}
+
+#[test]
+fn exclude_path() {
+    assert_eq!(sum!(map_not_serde, &foo()), 10001);
+}
+
+#[test]
+fn exclude_nested_path_matches_name_value() {
+    assert_eq!(sum!(map_no_defaults, &foo()), 10111);
+}
+
+#[test]
+fn exclude_multiple_patterns() {
+    assert_eq!(sum!(map_visible, &foo()), 1001);
+}
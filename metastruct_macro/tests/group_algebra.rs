@@ -0,0 +1,87 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(mappings(
+    map_included(include(a, d)),
+    map_hashing(groups(hashing)),
+    map_all_but_historical(groups(all - historical)),
+    map_legacy_but_historical(groups(legacy - historical)),
+    map_union(groups(union(hashing, historical))),
+    map_union_op(groups(hashing + legacy)),
+    map_intersection(groups(hashing & historical)),
+    map_difference(groups(difference(all, hashing, legacy))),
+    map_nested(groups((all - hashing) + (hashing & historical))),
+    map_include_and_group(include(a, b, c), groups(hashing)),
+))]
+pub struct Foo {
+    #[metastruct(groups(hashing))]
+    a: u64,
+    #[metastruct(groups(hashing, historical))]
+    b: u64,
+    #[metastruct(exclude_from(legacy))]
+    c: u64,
+    d: u64,
+}
+
+fn foo() -> Foo {
+    Foo {
+        a: 1,
+        b: 10,
+        c: 100,
+        d: 1000,
+    }
+}
+
+macro_rules! sum {
+    ($mapping:ident, $foo:expr) => {{
+        let mut total = 0;
+        $mapping!($foo, |_, x| total += *x);
+        total
+    }};
+}
+
+#[test]
+fn include() {
+    assert_eq!(sum!(map_included, &foo()), 1001);
+}
+
+#[test]
+fn declared_group_membership() {
+    assert_eq!(sum!(map_hashing, &foo()), 11);
+}
+
+#[test]
+fn all_minus_group() {
+    assert_eq!(sum!(map_all_but_historical, &foo()), 1101);
+}
+
+#[test]
+fn opt_out_group() {
+    // `legacy` is never declared by a field, so only `c` is excluded from it.
+    assert_eq!(sum!(map_legacy_but_historical, &foo()), 1001);
+}
+
+#[test]
+fn union() {
+    assert_eq!(sum!(map_union, &foo()), 11);
+    assert_eq!(sum!(map_union_op, &foo()), 1011);
+}
+
+#[test]
+fn intersection() {
+    assert_eq!(sum!(map_intersection, &foo()), 10);
+}
+
+#[test]
+fn difference() {
+    assert_eq!(sum!(map_difference, &foo()), 100);
+}
+
+#[test]
+fn nested() {
+    assert_eq!(sum!(map_nested, &foo()), 1110);
+}
+
+#[test]
+fn include_and_group() {
+    assert_eq!(sum!(map_include_and_group, &foo()), 11);
+}
@@ -1,3 +1,6 @@
+// The closure is instantiated once per field, so the cast is only redundant for some fields.
+#![allow(clippy::unnecessary_cast)]
+
 use metastruct_macro::metastruct;
 
 #[metastruct(mappings(map_foo_fields()))]
@@ -0,0 +1,84 @@
+use metastruct_macro::metastruct;
+use std::fmt::Display;
+
+#[metastruct(mappings(
+    map_config_limits(only_types(u64), method = "for_each_limit"),
+    map_config_limits_mut(only_types(u64), mutable, method = "for_each_limit_mut"),
+    map_config_fields(field_names, method = "for_each_field", method_trait = "Display"),
+    try_map_config_limits(only_types(u64), fallible, method = "try_for_each_limit"),
+))]
+pub struct Config {
+    pub name: String,
+    pub max_peers: u64,
+    pub max_blocks: u64,
+}
+
+fn config() -> Config {
+    Config {
+        name: "node".to_string(),
+        max_peers: 50,
+        max_blocks: 64,
+    }
+}
+
+#[test]
+fn homogeneous_method() {
+    let config = config();
+    let mut limits = vec![];
+    config.for_each_limit(|i, limit| limits.push((i, *limit)));
+    assert_eq!(limits, vec![(0, 50), (1, 64)]);
+}
+
+#[test]
+fn mutable_method() {
+    let mut config = config();
+    config.for_each_limit_mut(|_, limit| *limit *= 2);
+    assert_eq!((config.max_peers, config.max_blocks), (100, 128));
+}
+
+#[test]
+fn trait_object_method() {
+    fn describe(i: usize, name: &str, value: &dyn Display) -> String {
+        format!("{i}:{name}={value}")
+    }
+    let config = config();
+    let mut fields = vec![];
+    config.for_each_field(|i, name, value| fields.push(describe(i, name, value)));
+    assert_eq!(fields, ["0:name=node", "1:max_peers=50", "2:max_blocks=64"]);
+}
+
+#[test]
+fn fallible_method() {
+    let config = config();
+    let result = config.try_for_each_limit(|i, limit| {
+        if *limit > 60 {
+            return Err(i);
+        }
+        Ok(())
+    });
+    assert_eq!(result, Err(1));
+}
+
+#[test]
+fn macro_still_available() {
+    let mut count = 0;
+    map_config_fields!(&config(), |_, _, _| count += 1);
+    assert_eq!(count, 3);
+}
+
+#[metastruct(mappings(map_pair_fields(static_dispatch, method = "for_each")))]
+pub struct Pair<T> {
+    pub first: T,
+    pub second: T,
+}
+
+#[test]
+fn generic_struct_method() {
+    let pair = Pair {
+        first: "a",
+        second: "b",
+    };
+    let mut fields = vec![];
+    pair.for_each(|_, x| fields.push(*x));
+    assert_eq!(fields, ["a", "b"]);
+}
@@ -0,0 +1,93 @@
+// The closures convert fields of several types, some of which are already `u64`.
+#![allow(clippy::useless_conversion)]
+
+use metastruct_macro::metastruct;
+
+#[metastruct(mappings(
+    map_block_numbers(exclude(label), static_dispatch),
+    map_block_numbers_mut(exclude(label), mutable, static_dispatch),
+    map_block_fields(field_names, static_dispatch),
+    try_map_block_numbers(exclude(label), fallible, static_dispatch),
+))]
+pub struct Block {
+    pub slot: u64,
+    pub proposer: u32,
+    pub label: String,
+    pub flags: u8,
+}
+
+fn block() -> Block {
+    Block {
+        slot: 100,
+        proposer: 7,
+        label: "block".to_string(),
+        flags: 3,
+    }
+}
+
+#[test]
+fn sum_fields_of_different_types() {
+    let block = block();
+    let mut sum = 0;
+    map_block_numbers!(&block, |_, x| sum += u64::from(*x));
+    assert_eq!(sum, 110);
+}
+
+#[test]
+fn mutable_fields() {
+    let mut block = block();
+    map_block_numbers_mut!(&mut block, |_, x| *x *= 2);
+    assert_eq!((block.slot, block.proposer, block.flags), (200, 14, 6));
+}
+
+#[test]
+fn field_names_and_indices() {
+    let block = block();
+    let mut fields = vec![];
+    map_block_fields!(&block, |i, name, x| fields.push((i, name, x.to_string())));
+    assert_eq!(
+        fields,
+        vec![
+            (0, "slot", "100".to_string()),
+            (1, "proposer", "7".to_string()),
+            (2, "label", "block".to_string()),
+            (3, "flags", "3".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn fallible() {
+    fn check(block: &Block) -> Result<u64, String> {
+        let mut sum = 0;
+        try_map_block_numbers!(block, |i, x| {
+            if u64::from(*x) > 50 && i > 0 {
+                return Err(format!("field {i} out of range"));
+            }
+            sum += u64::from(*x);
+            Ok(())
+        });
+        Ok(sum)
+    }
+    assert_eq!(check(&block()), Ok(110));
+    let mut block = block();
+    block.flags = 99;
+    assert_eq!(check(&block), Err("field 2 out of range".to_string()));
+}
+
+#[metastruct(mappings(map_pair_fields(static_dispatch)))]
+pub struct Pair<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+#[test]
+fn generic_struct() {
+    let pair = Pair {
+        first: 1u8,
+        second: "two",
+    };
+    let mut fields = vec![];
+    map_pair_fields!(&pair, |_, x| fields.push(x.to_string()));
+    assert_eq!(fields, ["1", "two"]);
+}
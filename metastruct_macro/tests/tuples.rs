@@ -0,0 +1,63 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(tuples(tuple(), numeric(only_types(u64, u8))))]
+#[derive(Debug, Default, PartialEq)]
+pub struct Foo {
+    pub a: u64,
+    pub b: String,
+    pub c: u8,
+}
+
+#[test]
+fn aliases() {
+    let tuple: FooTuple = (1, "b".to_string(), 3);
+    let numeric: FooNumeric = (1, 3);
+    assert_eq!(Foo::from_tuple(tuple).into_numeric(), numeric);
+}
+
+#[test]
+fn round_trip() {
+    let foo = Foo {
+        a: 1,
+        b: "b".to_string(),
+        c: 3,
+    };
+    let (a, b, c) = foo.into_tuple();
+    assert_eq!((a, b.as_str(), c), (1, "b", 3));
+    assert_eq!(
+        Foo::from_numeric((1, 3)),
+        Foo {
+            a: 1,
+            b: String::new(),
+            c: 3
+        }
+    );
+}
+
+#[test]
+fn references() {
+    let mut foo = Foo::default();
+    match foo.as_numeric_ref() {
+        (0, 0) => {}
+        _ => panic!("expected defaults"),
+    }
+    let (a, b, _) = foo.as_tuple_mut();
+    *a = 5;
+    b.push('x');
+    assert_eq!(foo.as_tuple_ref(), (&5, &"x".to_string(), &0));
+}
+
+#[metastruct(tuples(values(exclude(label))))]
+pub struct Labelled<'a, T, const N: usize> {
+    pub label: &'a str,
+    pub values: [T; N],
+    pub count: usize,
+}
+
+#[test]
+fn generic_alias() {
+    let labelled = Labelled::<u8, 2>::from_values(([1, 2], 2));
+    assert_eq!(labelled.label, "");
+    let values: LabelledValues<u8, 2> = labelled.into_values();
+    assert_eq!(values, ([1, 2], 2));
+}
@@ -0,0 +1,70 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(mappings(
+    map_u64_fields(only_types(u64)),
+    map_byte_fields(only_types(u8, Vec<u8>)),
+    map_non_string_fields(except_types(String, Vec<u8>)),
+    map_pub_fields(visibility = "pub"),
+    map_crate_fields(visibility = "pub(crate)"),
+    map_private_fields(visibility = "private"),
+    map_pub_u64_fields(only_types(u64), visibility = "pub", exclude(c)),
+))]
+pub struct Foo {
+    pub a: u64,
+    pub(crate) b: u8,
+    pub c: u64,
+    d: u64,
+    pub e: String,
+    pub(crate) f: Vec<u8>,
+}
+
+fn foo() -> Foo {
+    Foo {
+        a: 1,
+        b: 2,
+        c: 3,
+        d: 4,
+        e: "e".to_string(),
+        f: vec![5, 6],
+    }
+}
+
+#[test]
+fn only_types() {
+    let mut total = 0;
+    map_u64_fields!(&foo(), |_, x| total += *x);
+    assert_eq!(total, 8);
+
+    let mut lens = vec![];
+    map_byte_fields!(&foo(), |_, x| lens.push(std::mem::size_of_val(x)));
+    assert_eq!(lens, vec![1, std::mem::size_of::<Vec<u8>>()]);
+}
+
+#[test]
+fn except_types() {
+    let mut count = 0;
+    map_non_string_fields!(&foo(), |_, _| count += 1);
+    assert_eq!(count, 4);
+}
+
+#[test]
+fn visibility() {
+    let mut names = vec![];
+    map_pub_fields!(&foo(), |i, _| names.push(i));
+    assert_eq!(names, vec![0, 1, 2]);
+
+    let mut count = 0;
+    map_crate_fields!(&foo(), |_, _| count += 1);
+    assert_eq!(count, 2);
+
+    let mut total = 0;
+    map_private_fields!(&foo(), |_, x| total += *x);
+    assert_eq!(total, 4);
+}
+
+#[test]
+fn combined_with_exclude() {
+    let mut total = 0;
+    map_pub_u64_fields!(&foo(), |_, x| total += *x);
+    assert_eq!(total, 1);
+}
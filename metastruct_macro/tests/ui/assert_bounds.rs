@@ -0,0 +1,25 @@
+use metastruct_macro::metastruct;
+
+pub trait Checked {}
+
+impl Checked for u64 {}
+
+pub struct Opaque;
+
+#[metastruct(group_bounds(checked = "Checked"))]
+pub struct Foo {
+    #[metastruct(groups(checked))]
+    pub id: u64,
+    #[metastruct(groups(checked))]
+    pub opaque: Opaque,
+    pub name: String,
+}
+
+#[metastruct(mappings(map_bar_fields(assert_bounds = "Checked", exclude(name))))]
+pub struct Bar {
+    pub id: u64,
+    pub name: String,
+    pub opaque: Opaque,
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+// Closures which cannot take a field are reported at the field declaration.
+use metastruct_macro::metastruct;
+
+#[metastruct(mappings(map_block_fields()))]
+pub struct Block {
+    pub slot: u64,
+    pub label: String,
+}
+
+fn main() {
+    let block = Block {
+        slot: 1,
+        label: String::new(),
+    };
+    let mut sum = 0;
+    map_block_fields!(&block, |_, x: &u64| sum += *x);
+}
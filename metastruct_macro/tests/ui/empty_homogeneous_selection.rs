@@ -0,0 +1,9 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(iters(counters(only_types(u64))))]
+pub struct Foo {
+    pub a: u8,
+    pub b: String,
+}
+
+fn main() {}
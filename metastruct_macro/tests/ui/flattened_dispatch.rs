@@ -0,0 +1,15 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(num_fields(all()))]
+pub struct Inner {
+    pub x: u64,
+}
+
+#[metastruct(dispatch(map_outer_at()))]
+pub struct Outer {
+    pub a: u64,
+    #[metastruct(flatten(map_outer_at = map_inner_at))]
+    pub inner: Inner,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(mappings(map_inner_fields()))]
+pub struct Inner {
+    pub x: u64,
+}
+
+#[metastruct(field_enum, mappings(map_outer_fields(field_enum)))]
+pub struct Outer {
+    pub a: u64,
+    #[metastruct(flatten(map_outer_fields = map_inner_fields))]
+    pub inner: Inner,
+}
+
+fn main() {}
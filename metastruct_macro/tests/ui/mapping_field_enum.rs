@@ -0,0 +1,14 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(mappings(map_config_fields(field_enum)))]
+pub struct Config {
+    pub a: u64,
+}
+
+#[metastruct(field_enum(exclude(a)), mappings(map_state_fields(field_enum)))]
+pub struct State {
+    pub a: u64,
+    pub b: u64,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use metastruct_macro::metastruct;
+
+fn enabled(_: &()) -> bool {
+    true
+}
+
+#[metastruct(num_fields(conditional()))]
+pub struct Foo {
+    pub a: u64,
+    #[metastruct(only_if(conditional = enabled))]
+    pub b: u64,
+}
+
+fn main() {}
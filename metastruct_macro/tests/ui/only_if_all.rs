@@ -0,0 +1,19 @@
+use metastruct_macro::metastruct;
+
+fn enabled(_: &()) -> bool {
+    true
+}
+
+#[metastruct(
+    mappings(map_current(groups(all - historical))),
+    num_fields(all())
+)]
+pub struct Foo {
+    pub a: u64,
+    #[metastruct(only_if(all = enabled))]
+    pub b: u64,
+    #[metastruct(groups(historical))]
+    pub c: u64,
+}
+
+fn main() {}
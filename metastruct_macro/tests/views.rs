@@ -0,0 +1,179 @@
+use metastruct_macro::metastruct;
+
+#[metastruct(views(
+    hashing(groups(hashing)),
+    counters(only_types(u64), exclude(c)),
+    flags(only_types(bool))
+))]
+pub struct Foo {
+    #[metastruct(groups(hashing))]
+    pub a: u64,
+    #[metastruct(groups(hashing))]
+    b: String,
+    c: u64,
+    pub d: u64,
+}
+
+fn foo() -> Foo {
+    Foo {
+        a: 1,
+        b: "b".to_string(),
+        c: 3,
+        d: 4,
+    }
+}
+
+fn hash_len(view: FooHashingRef) -> usize {
+    *view.a as usize + view.b.len()
+}
+
+fn bump(view: FooCountersMut) {
+    *view.a += 10;
+    *view.d += 10;
+}
+
+#[test]
+fn shared_view() {
+    let foo = foo();
+    assert_eq!(hash_len(foo.as_hashing_ref()), 2);
+}
+
+#[test]
+fn empty_view() {
+    let mut foo = foo();
+    let _: FooFlagsRef = foo.as_flags_ref();
+    let _: FooFlagsMut = foo.as_flags_mut();
+}
+
+#[test]
+fn mutable_view() {
+    let mut foo = foo();
+    bump(foo.as_counters_mut());
+    assert_eq!((foo.a, foo.c, foo.d), (11, 3, 14));
+}
+
+#[test]
+fn view_outlives_method_call() {
+    let mut foo = foo();
+    let view = foo.as_hashing_mut();
+    view.b.push('!');
+    *view.a = 0;
+    assert_eq!(foo.b, "b!");
+    assert_eq!(foo.a, 0);
+}
+
+#[metastruct(views(values(exclude(name))))]
+pub struct Generic<'a, T: Clone>
+where
+    T: Default,
+{
+    pub name: &'a str,
+    pub x: T,
+    pub y: T,
+}
+
+#[test]
+fn generic_view() {
+    let name = String::from("gen");
+    let mut g = Generic {
+        name: &name,
+        x: 1u8,
+        y: 2,
+    };
+    let view = g.as_values_mut();
+    std::mem::swap(view.x, view.y);
+    assert_eq!((g.x, g.y), (2, 1));
+    assert_eq!(g.as_values_ref().x, &2);
+}
+
+#[metastruct(
+    views(
+        config(include(limit, name)),
+        state(include(count, history)),
+        history(include(history)),
+    ),
+    splits(
+        update(views(state, config), mutable(state)),
+        inspect(views(config, history)),
+    )
+)]
+pub struct Counter {
+    limit: usize,
+    name: String,
+    count: usize,
+    history: Vec<usize>,
+}
+
+#[test]
+fn split_mutable_and_shared() {
+    let mut counter = Counter {
+        limit: 3,
+        name: "counter".to_string(),
+        count: 0,
+        history: vec![],
+    };
+    let (state, config) = counter.split_update_mut();
+    while *state.count < *config.limit {
+        state.history.push(*state.count);
+        *state.count += config.name.len();
+    }
+    assert_eq!(counter.count, 7);
+    assert_eq!(counter.history, vec![0]);
+}
+
+#[test]
+fn split_shared_only() {
+    let mut counter = Counter {
+        limit: 3,
+        name: "counter".to_string(),
+        count: 0,
+        history: vec![1, 2],
+    };
+    let (config, history) = counter.split_inspect_mut();
+    assert_eq!(*config.limit, 3);
+    assert_eq!(history.history, &vec![1, 2]);
+}
+
+#[metastruct(
+    views(
+        hot(include(buffer, cursor), owned),
+        cold(exclude(buffer, cursor), owned),
+    ),
+    partitions(parts(views(hot, cold)))
+)]
+#[derive(Debug, PartialEq)]
+pub struct Job<T> {
+    pub id: u64,
+    buffer: Vec<T>,
+    cursor: usize,
+    label: String,
+}
+
+#[test]
+fn partition_round_trip() {
+    let job = Job {
+        id: 7,
+        buffer: vec![1u8, 2, 3],
+        cursor: 1,
+        label: "job".to_string(),
+    };
+    let (mut hot, cold) = job.into_parts();
+
+    let handle = std::thread::spawn(move || {
+        hot.buffer.push(4);
+        hot.cursor += 2;
+        hot
+    });
+    assert_eq!((cold.id, cold.label.as_str()), (7, "job"));
+    let hot: JobHot<u8> = handle.join().unwrap();
+
+    assert_eq!(
+        Job::from_parts(hot, cold),
+        Job {
+            id: 7,
+            buffer: vec![1, 2, 3, 4],
+            cursor: 3,
+            label: "job".to_string(),
+        }
+    );
+}